@@ -0,0 +1,735 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::restriction,
+    clippy::nursery,
+    clippy::cargo
+)]
+#![allow(clippy::missing_docs_in_private_items)]
+#![allow(clippy::implicit_return)]
+#![allow(clippy::question_mark_used)]
+#![allow(clippy::cargo_common_metadata)]
+#![allow(clippy::single_call_fn)]
+#![allow(clippy::std_instead_of_core)]
+#![allow(clippy::blanket_clippy_restriction_lints)]
+#![allow(clippy::separated_literal_suffix)]
+#![allow(clippy::allow_attributes_without_reason)]
+#![allow(clippy::missing_inline_in_public_items)]
+
+//! Derives `FromJson` and `ToJson` of the `parsing` crate, with only the
+//! compiler's `proc_macro` API.
+//!
+//! Container attributes: `#[json(rename_all = "camelCase")]` (also
+//! `snake_case`, `PascalCase`, `kebab-case`, `SCREAMING_SNAKE_CASE`,
+//! `lowercase`), and on enums `#[json(tag = "type")]` for internal tagging or
+//! `#[json(tag = "t", content = "c")]` for adjacent tagging, external tagging
+//! being the default. `#[json(crate = "path")]` names the `parsing` crate when
+//! it is not `::parsing`, such as `crate` within it.
+//!
+//! Named fields accept `rename = "name"`, `default`, `skip` and `flatten`.
+//! Variants accept `rename` and `rename_all`, the latter for their fields.
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Name the `parsing` crate is imported as, around each generated impl.
+const KRATE: &str = "__parsing";
+const CONV: &str = "__parsing::convert";
+const JSON: &str = "__parsing::json";
+
+#[derive(Default)]
+struct Attrs {
+    content: Option<String>,
+    default: bool,
+    flatten: bool,
+    krate: Option<String>,
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    tag: Option<String>,
+}
+
+struct Field {
+    attrs: Attrs,
+    /// `None` for tuple fields.
+    name: Option<String>,
+    ty: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Named,
+    Tuple,
+    Unit,
+}
+
+/// Fields of a struct or of a variant, none for a unit.
+struct Shape {
+    fields: Vec<Field>,
+    kind: Kind,
+}
+
+struct Variant {
+    attrs: Attrs,
+    name: String,
+    shape: Shape,
+}
+
+enum Body {
+    Enum(Vec<Variant>),
+    Struct(Shape),
+}
+
+#[derive(Default)]
+struct Generics {
+    /// Parameters without bounds, for `Name<...>`.
+    args: Vec<String>,
+    /// Parameters with their bounds but not their defaults, for `impl<...>`.
+    params: Vec<String>,
+    /// Type parameters, that must implement the derived trait.
+    types: Vec<String>,
+}
+
+struct Item {
+    attrs: Attrs,
+    body: Body,
+    generics: Generics,
+    name: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    From,
+    To,
+}
+
+type DResult<T> = Result<T, String>;
+
+fn tokens_to_string(tokens: &[TokenTree]) -> String {
+    tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token.cloned(), Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+fn is_ident(token: Option<&TokenTree>, name: &str) -> bool {
+    matches!(token.cloned(), Some(TokenTree::Ident(ident)) if ident.to_string() == name)
+}
+
+fn is_group(token: Option<&TokenTree>, delimiter: Delimiter) -> bool {
+    matches!(token.cloned(), Some(TokenTree::Group(group)) if group.delimiter() == delimiter)
+}
+
+/// Splits tokens on the commas that are not nested in `<...>`, as angle
+/// brackets are plain punctuation rather than groups.
+fn split_commas(tokens: &[TokenTree]) -> Vec<Vec<TokenTree>> {
+    let mut res = vec![vec![]];
+    let mut depth = 0_usize;
+    let mut prev_joint_dash = false;
+    for token in tokens {
+        let mut joint_dash = false;
+        if let TokenTree::Punct(punct) = token.clone() {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    res.push(vec![]);
+                    continue;
+                }
+                '<' => depth = depth.saturating_add(1),
+                '>' if !prev_joint_dash => depth = depth.saturating_sub(1),
+                '-' => joint_dash = punct.spacing() == Spacing::Joint,
+                _ => (),
+            }
+        }
+        prev_joint_dash = joint_dash;
+        if let Some(last) = res.last_mut() {
+            last.push(token.clone());
+        }
+    }
+    res.retain(|part| !part.is_empty());
+    res
+}
+
+fn unquote(lit: &str) -> DResult<String> {
+    lit.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| format!("expected a string literal, found {lit}"))
+}
+
+fn parse_json_attr(stream: TokenStream, attrs: &mut Attrs) -> DResult<()> {
+    let tokens = stream.into_iter().collect::<Vec<_>>();
+    for part in split_commas(&tokens) {
+        let Some(TokenTree::Ident(key)) = part.first().cloned() else {
+            return Err(format!("invalid json attribute `{}`", tokens_to_string(&part)));
+        };
+        let value = match (part.get(1).cloned(), part.get(2).cloned()) {
+            (Some(TokenTree::Punct(punct)), Some(TokenTree::Literal(lit))) if punct.as_char() == '=' => {
+                Some(unquote(&lit.to_string())?)
+            }
+            (None, _) => None,
+            _ => return Err(format!("invalid json attribute `{}`", tokens_to_string(&part))),
+        };
+        match (key.to_string().as_str(), value) {
+            ("crate", Some(val)) => attrs.krate = Some(val),
+            ("rename", Some(val)) => attrs.rename = Some(val),
+            ("rename_all", Some(val)) => {
+                rename_case(&val, "check")?;
+                attrs.rename_all = Some(val);
+            }
+            ("tag", Some(val)) => attrs.tag = Some(val),
+            ("content", Some(val)) => attrs.content = Some(val),
+            ("default", None) => attrs.default = true,
+            ("skip", None) => attrs.skip = true,
+            ("flatten", None) => attrs.flatten = true,
+            (other, _) => return Err(format!("unknown json attribute `{other}`")),
+        }
+    }
+    Ok(())
+}
+
+/// Consumes the outer attributes at `pos`, keeping the `#[json(...)]` ones.
+fn parse_attrs(tokens: &[TokenTree], pos: &mut usize) -> DResult<Attrs> {
+    let mut attrs = Attrs::default();
+    while is_punct(tokens.get(*pos), '#') {
+        let Some(TokenTree::Group(group)) = tokens.get(pos.saturating_add(1)).cloned() else {
+            return Err("expected an attribute after `#`".to_owned());
+        };
+        let inner = group.stream().into_iter().collect::<Vec<_>>();
+        if is_ident(inner.first(), "json") {
+            if let Some(TokenTree::Group(args)) = inner.get(1).cloned() {
+                parse_json_attr(args.stream(), &mut attrs)?;
+            }
+        }
+        *pos = pos.saturating_add(2);
+    }
+    Ok(attrs)
+}
+
+fn skip_visibility(tokens: &[TokenTree], pos: &mut usize) {
+    if is_ident(tokens.get(*pos), "pub") {
+        *pos = pos.saturating_add(1);
+        if is_group(tokens.get(*pos), Delimiter::Parenthesis) {
+            *pos = pos.saturating_add(1);
+        }
+    }
+}
+
+fn parse_fields(stream: TokenStream, named: bool) -> DResult<Vec<Field>> {
+    let tokens = stream.into_iter().collect::<Vec<_>>();
+    split_commas(&tokens)
+        .into_iter()
+        .map(|part| {
+            let mut pos = 0;
+            let attrs = parse_attrs(&part, &mut pos)?;
+            skip_visibility(&part, &mut pos);
+            let name = if named {
+                let Some(TokenTree::Ident(ident)) = part.get(pos).cloned() else {
+                    return Err("expected a field name".to_owned());
+                };
+                if !is_punct(part.get(pos.saturating_add(1)), ':') {
+                    return Err(format!("expected `:` after field {ident}"));
+                }
+                pos = pos.saturating_add(2);
+                Some(ident.to_string())
+            } else {
+                // Tuple fields are positional: nothing to rename, and every
+                // element must be present.
+                if attrs.skip || attrs.default || attrs.flatten || attrs.rename.is_some() {
+                    return Err("json field attributes only apply to named fields".to_owned());
+                }
+                None
+            };
+            let ty = tokens_to_string(part.get(pos..).unwrap_or_default());
+            Ok(Field { attrs, name, ty })
+        })
+        .collect()
+}
+
+fn parse_shape(token: Option<&TokenTree>) -> DResult<Shape> {
+    let (kind, fields) = match token.cloned() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+            (Kind::Named, parse_fields(group.stream(), true)?)
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            (Kind::Tuple, parse_fields(group.stream(), false)?)
+        }
+        _ => (Kind::Unit, vec![]),
+    };
+    Ok(Shape { fields, kind })
+}
+
+fn parse_variants(stream: TokenStream) -> DResult<Vec<Variant>> {
+    let tokens = stream.into_iter().collect::<Vec<_>>();
+    split_commas(&tokens)
+        .into_iter()
+        .map(|part| {
+            let mut pos = 0;
+            let attrs = parse_attrs(&part, &mut pos)?;
+            let Some(TokenTree::Ident(ident)) = part.get(pos).cloned() else {
+                return Err("expected a variant name".to_owned());
+            };
+            Ok(Variant {
+                attrs,
+                name: ident.to_string(),
+                shape: parse_shape(part.get(pos.saturating_add(1)))?,
+            })
+        })
+        .collect()
+}
+
+/// A generic parameter without its `= default`, which `impl<...>` does not
+/// accept.
+fn without_default(param: &[TokenTree]) -> &[TokenTree] {
+    let mut depth = 0_usize;
+    for (idx, token) in param.iter().enumerate() {
+        if let TokenTree::Punct(punct) = token.clone() {
+            match punct.as_char() {
+                '<' => depth = depth.saturating_add(1),
+                '>' => depth = depth.saturating_sub(1),
+                '=' if depth == 0 => return param.get(..idx).unwrap_or_default(),
+                _ => (),
+            }
+        }
+    }
+    param
+}
+
+fn parse_generics(tokens: &[TokenTree], pos: &mut usize) -> DResult<Generics> {
+    let mut generics = Generics::default();
+    if !is_punct(tokens.get(*pos), '<') {
+        return Ok(generics);
+    }
+    let start = pos.saturating_add(1);
+    let mut depth = 1_usize;
+    while depth > 0 {
+        *pos = pos.saturating_add(1);
+        match tokens.get(*pos).cloned() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => depth = depth.saturating_add(1),
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '>' => depth = depth.saturating_sub(1),
+            Some(_) => (),
+            None => return Err("unterminated generics".to_owned()),
+        }
+    }
+    for param in split_commas(tokens.get(start..*pos).unwrap_or_default()) {
+        generics.params.push(tokens_to_string(without_default(&param)));
+        match (param.first().cloned(), param.get(1)) {
+            (Some(TokenTree::Punct(punct)), Some(lifetime)) if punct.as_char() == '\'' => {
+                generics.args.push(format!("'{lifetime}"));
+            }
+            (Some(TokenTree::Ident(kw)), Some(name)) if kw.to_string() == "const" => {
+                generics.args.push(name.to_string());
+            }
+            (Some(name), _) => {
+                generics.args.push(name.to_string());
+                generics.types.push(name.to_string());
+            }
+            (None, _) => (),
+        }
+    }
+    *pos = pos.saturating_add(1);
+    Ok(generics)
+}
+
+fn parse_item(input: TokenStream) -> DResult<Item> {
+    let tokens = input.into_iter().collect::<Vec<_>>();
+    let mut pos = 0;
+    let attrs = parse_attrs(&tokens, &mut pos)?;
+    skip_visibility(&tokens, &mut pos);
+    let is_enum = match tokens.get(pos).cloned() {
+        Some(TokenTree::Ident(kw)) if kw.to_string() == "struct" => false,
+        Some(TokenTree::Ident(kw)) if kw.to_string() == "enum" => true,
+        _ => return Err("FromJson and ToJson can only be derived for structs and enums".to_owned()),
+    };
+    let Some(TokenTree::Ident(name)) = tokens.get(pos.saturating_add(1)).cloned() else {
+        return Err("expected a type name".to_owned());
+    };
+    pos = pos.saturating_add(2);
+    let generics = parse_generics(&tokens, &mut pos)?;
+    if is_ident(tokens.get(pos), "where") {
+        return Err("where clauses are not supported by the json derives".to_owned());
+    }
+    let body = if is_enum {
+        match tokens.get(pos).cloned() {
+            Some(TokenTree::Group(group)) => Body::Enum(parse_variants(group.stream())?),
+            _ => return Err("expected the variants of the enum".to_owned()),
+        }
+    } else {
+        Body::Struct(parse_shape(tokens.get(pos))?)
+    };
+    Ok(Item {
+        attrs,
+        body,
+        generics,
+        name: name.to_string(),
+    })
+}
+
+/// Splits an identifier into lowercase words, on underscores and on the
+/// lowercase to uppercase transitions.
+fn words(ident: &str) -> Vec<String> {
+    let mut res = vec![];
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for ch in ident.chars() {
+        if ch == '_' {
+            if !current.is_empty() {
+                res.push(current.clone());
+                current.clear();
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_uppercase() && prev_lower {
+            res.push(current.clone());
+            current.clear();
+        }
+        prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        current.extend(ch.to_lowercase());
+    }
+    if !current.is_empty() {
+        res.push(current);
+    }
+    res
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn rename_case(case: &str, ident: &str) -> DResult<String> {
+    let parts = words(ident);
+    Ok(match case {
+        "camelCase" => parts
+            .iter()
+            .enumerate()
+            .map(|(idx, word)| if idx == 0 { word.clone() } else { capitalize(word) })
+            .collect(),
+        "PascalCase" => parts.iter().map(|word| capitalize(word)).collect(),
+        "snake_case" => parts.join("_"),
+        "kebab-case" => parts.join("-"),
+        "SCREAMING_SNAKE_CASE" => parts.join("_").to_uppercase(),
+        "lowercase" => parts.concat(),
+        _ => return Err(format!("unsupported rename_all case `{case}`")),
+    })
+}
+
+fn json_name(ident: &str, attrs: &Attrs, rename_all: Option<&String>) -> DResult<String> {
+    let unraw = ident.strip_prefix("r#").unwrap_or(ident);
+    match (attrs.rename.as_deref(), rename_all) {
+        (Some(name), _) => Ok(name.to_owned()),
+        (None, Some(case)) => rename_case(case, unraw),
+        (None, None) => Ok(unraw.to_owned()),
+    }
+}
+
+/// `Self { a: ..., b: ... }` read from the object `obj`, the whole value being
+/// `value` for flattened fields.
+fn named_from(ctor: &str, fields: &[Field], rename_all: Option<&String>) -> DResult<String> {
+    let mut inits = vec![];
+    for field in fields {
+        let name = field.name.clone().unwrap_or_default();
+        let key = json_name(&name, &field.attrs, rename_all)?;
+        inits.push(if field.attrs.skip {
+            format!("{name}: ::core::default::Default::default()")
+        } else if field.attrs.flatten {
+            format!("{name}: {CONV}::FromJson::from_json(value)?")
+        } else if field.attrs.default {
+            format!("{name}: {CONV}::field_or_default(obj, {key:?})?")
+        } else {
+            format!("{name}: {CONV}::field(obj, {key:?})?")
+        });
+    }
+    Ok(format!(
+        "{{ let obj = {CONV}::object(value)?; ::core::result::Result::Ok({ctor} {{ {} }}) }}",
+        inits.join(", ")
+    ))
+}
+
+/// Object built from fields, each accessed through `access(name)`.
+fn named_to(fields: &[Field], rename_all: Option<&String>, prefix: &str, access: impl Fn(&str) -> String) -> DResult<String> {
+    let mut pushes = vec![prefix.to_owned()];
+    for field in fields.iter().filter(|field| !field.attrs.skip) {
+        let name = field.name.clone().unwrap_or_default();
+        let key = json_name(&name, &field.attrs, rename_all)?;
+        pushes.push(if field.attrs.flatten {
+            format!("{CONV}::flatten_into(&mut obj, {CONV}::ToJson::to_json({}));", access(&name))
+        } else {
+            format!("{CONV}::push_field(&mut obj, {key:?}, {CONV}::ToJson::to_json({}));", access(&name))
+        });
+    }
+    Ok(format!(
+        "{{ let mut obj = ::std::vec::Vec::new(); {} {JSON}::ParsedValue::Object(obj) }}",
+        pushes.join(" ")
+    ))
+}
+
+fn tuple_from(ctor: &str, fields: &[Field]) -> String {
+    if fields.len() == 1 {
+        return format!("::core::result::Result::Ok({ctor}({CONV}::FromJson::from_json(value)?))");
+    }
+    let elems = (0..fields.len())
+        .map(|idx| format!("{CONV}::element(arr, {idx})?"))
+        .collect::<Vec<_>>();
+    format!(
+        "{{ let arr = {CONV}::array(value, {})?; ::core::result::Result::Ok({ctor}({})) }}",
+        fields.len(),
+        elems.join(", ")
+    )
+}
+
+fn tuple_to(bindings: &[String]) -> String {
+    match (bindings.len(), bindings.first()) {
+        (1, Some(single)) => format!("{CONV}::ToJson::to_json({single})"),
+        _ => format!(
+            "{JSON}::ParsedValue::Array(::std::vec![{}])",
+            bindings
+                .iter()
+                .map(|binding| format!("{CONV}::ToJson::to_json({binding})"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn struct_from(shape: &Shape, attrs: &Attrs) -> DResult<String> {
+    Ok(match shape.kind {
+        Kind::Named => named_from("Self", &shape.fields, attrs.rename_all.as_ref())?,
+        Kind::Tuple => tuple_from("Self", &shape.fields),
+        Kind::Unit => "::core::result::Result::Ok(Self)".to_owned(),
+    })
+}
+
+fn struct_to(shape: &Shape, attrs: &Attrs) -> DResult<String> {
+    Ok(match shape.kind {
+        Kind::Named => named_to(&shape.fields, attrs.rename_all.as_ref(), "", |name| format!("&self.{name}"))?,
+        Kind::Tuple => tuple_to(&(0..shape.fields.len()).map(|idx| format!("&self.{idx}")).collect::<Vec<_>>()),
+        Kind::Unit => format!("{JSON}::ParsedValue::null()"),
+    })
+}
+
+/// Builds a variant from its content, a `&ParsedValue` named `value`.
+fn variant_from(variant: &Variant) -> DResult<String> {
+    let ctor = format!("Self::{}", variant.name);
+    let fields = &variant.shape.fields;
+    Ok(match variant.shape.kind {
+        Kind::Named => named_from(&ctor, fields, variant.attrs.rename_all.as_ref())?,
+        Kind::Tuple => tuple_from(&ctor, fields),
+        Kind::Unit => format!("::core::result::Result::Ok({ctor})"),
+    })
+}
+
+/// Pattern binding the fields of a variant, and the content they serialize to.
+fn variant_to(variant: &Variant, prefix: &str) -> DResult<(String, String)> {
+    let name = &variant.name;
+    let fields = &variant.shape.fields;
+    Ok(match variant.shape.kind {
+        Kind::Named => {
+            let bound = fields
+                .iter()
+                .filter(|field| !field.attrs.skip)
+                .map(|field| format!("{},", field.name.clone().unwrap_or_default()))
+                .collect::<Vec<_>>();
+            (
+                format!("Self::{name} {{ {} .. }}", bound.concat()),
+                named_to(fields, variant.attrs.rename_all.as_ref(), prefix, ToOwned::to_owned)?,
+            )
+        }
+        Kind::Tuple => {
+            let bindings = (0..fields.len()).map(|idx| format!("field{idx}")).collect::<Vec<_>>();
+            (format!("Self::{name}({})", bindings.join(", ")), tuple_to(&bindings))
+        }
+        Kind::Unit => (format!("Self::{name}"), format!("{JSON}::ParsedValue::null()")),
+    })
+}
+
+/// Whether a type is known to serialize to something else than an object,
+/// from its last path segment: scalars, strings and sequences.
+fn not_an_object(ty: &str) -> bool {
+    let mut rest = ty.trim_start_matches(['&', ' ']);
+    if rest.starts_with('\'') {
+        rest = rest.split_once(' ').map_or("", |(_, after)| after);
+    }
+    rest = rest.strip_prefix("mut ").unwrap_or(rest).trim();
+    if rest.starts_with(['[', '(']) {
+        return true;
+    }
+    let (path, args) = rest.split_once('<').unwrap_or((rest, ""));
+    match path.rsplit("::").next().unwrap_or(path).trim() {
+        "Box" | "Rc" | "Arc" => not_an_object(args.rsplit_once('>').map_or(args, |(inner, _)| inner)),
+        "bool" | "char" | "str" | "String" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32"
+        | "i64" | "i128" | "isize" | "f32" | "f64" | "Vec" | "VecDeque" | "Option" | "HashSet" | "BTreeSet" => true,
+        _ => false,
+    }
+}
+
+/// Internally tagged variants carry the tag beside their fields, so they need
+/// named fields or a single field that is an object.
+fn check_internal(variant: &Variant, attrs: &Attrs) -> DResult<()> {
+    if attrs.tag.is_none() || attrs.content.is_some() || variant.shape.kind != Kind::Tuple {
+        return Ok(());
+    }
+    match (variant.shape.fields.len(), variant.shape.fields.first()) {
+        (1, Some(field)) if not_an_object(&field.ty) => Err(format!(
+            "internally tagged variant {} holds a `{}`, which is not an object: use `content` for adjacent tagging",
+            variant.name, field.ty
+        )),
+        (1, _) => Ok(()),
+        _ => Err(format!(
+            "internally tagged variant {} must have named fields or a single field",
+            variant.name
+        )),
+    }
+}
+
+fn enum_from(variants: &[Variant], attrs: &Attrs) -> DResult<String> {
+    let mut names = vec![];
+    let mut arms = vec![];
+    for variant in variants {
+        let name = json_name(&variant.name, &variant.attrs, attrs.rename_all.as_ref())?;
+        let build = variant_from(variant)?;
+        check_internal(variant, attrs)?;
+        arms.push(match (attrs.tag.as_deref(), attrs.content.as_deref(), variant.shape.kind) {
+            // The content of a newtype is read from the whole object, beside
+            // the tag.
+            (Some(_), None, _) | (_, _, Kind::Unit) => format!("{name:?} => {build},"),
+            (_, content, _) => {
+                let key = content.map_or_else(|| name.clone(), ToOwned::to_owned);
+                format!(
+                    "{name:?} => (|| -> {CONV}::CResult<Self> {{ let value = {CONV}::content(content, {name:?})?; {build} }})()\
+                     .map_err(|err| err.at_key({key:?})),"
+                )
+            }
+        });
+        names.push(format!("{name:?}"));
+    }
+    let split = match (attrs.tag.as_deref(), attrs.content.as_deref()) {
+        (None, None) => format!("let (tag, content) = {CONV}::external(value)?;"),
+        (Some(tag), None) => format!("let tag: ::std::string::String = {CONV}::field({CONV}::object(value)?, {tag:?})?;"),
+        (Some(tag), Some(content)) => format!(
+            "let obj = {CONV}::object(value)?; \
+             let tag: ::std::string::String = {CONV}::field(obj, {tag:?})?; \
+             let content = {CONV}::member(obj, {content:?});"
+        ),
+        (None, Some(_)) => return Err("`content` requires `tag`".to_owned()),
+    };
+    Ok(format!(
+        "{split} match tag.as_str() {{ {} other => ::core::result::Result::Err({CONV}::unknown_variant(other, &[{}])), }}",
+        arms.join(" "),
+        names.join(", ")
+    ))
+}
+
+fn enum_to(variants: &[Variant], attrs: &Attrs) -> DResult<String> {
+    let mut arms = vec![];
+    for variant in variants {
+        let name = json_name(&variant.name, &variant.attrs, attrs.rename_all.as_ref())?;
+        check_internal(variant, attrs)?;
+        let body = match (attrs.tag.as_deref(), attrs.content.as_deref(), variant.shape.kind) {
+            (None, None, Kind::Unit) => format!("{JSON}::ParsedValue::string({name:?})"),
+            (None, None, _) => {
+                let (pattern, content) = variant_to(variant, "")?;
+                arms.push(format!("{pattern} => {CONV}::single({name:?}, {content}),"));
+                continue;
+            }
+            (Some(tag), None, Kind::Named) => {
+                let prefix = format!("{CONV}::push_field(&mut obj, {tag:?}, {JSON}::ParsedValue::string({name:?}));");
+                let (pattern, content) = variant_to(variant, &prefix)?;
+                arms.push(format!("{pattern} => {content},"));
+                continue;
+            }
+            (Some(tag), None, _) => {
+                let (pattern, fields) = variant_to(variant, "")?;
+                let content = if variant.shape.kind == Kind::Unit {
+                    format!("{JSON}::ParsedValue::Object(::std::vec::Vec::new())")
+                } else {
+                    fields
+                };
+                arms.push(format!("{pattern} => {CONV}::tagged({tag:?}, {name:?}, {content}),"));
+                continue;
+            }
+            (Some(tag), Some(key), kind) => {
+                let (pattern, content) = variant_to(variant, "")?;
+                let push = if kind == Kind::Unit {
+                    String::new()
+                } else {
+                    format!("{CONV}::push_field(&mut obj, {key:?}, {content});")
+                };
+                arms.push(format!(
+                    "{pattern} => {{ let mut obj = ::std::vec::Vec::new(); \
+                     {CONV}::push_field(&mut obj, {tag:?}, {JSON}::ParsedValue::string({name:?})); \
+                     {push} {JSON}::ParsedValue::Object(obj) }},"
+                ));
+                continue;
+            }
+            (None, Some(_), _) => return Err("`content` requires `tag`".to_owned()),
+        };
+        arms.push(format!("Self::{} => {body},", variant.name));
+    }
+    Ok(format!("match self {{ {} }}", arms.join(" ")))
+}
+
+fn generate(item: Item, mode: Mode) -> DResult<String> {
+    let (trait_name, signature, body) = match (mode, item.body) {
+        (Mode::From, Body::Struct(shape)) => (
+            "FromJson",
+            format!("fn from_json(value: &{JSON}::ParsedValue) -> {CONV}::CResult<Self>"),
+            struct_from(&shape, &item.attrs)?,
+        ),
+        (Mode::From, Body::Enum(variants)) => (
+            "FromJson",
+            format!("fn from_json(value: &{JSON}::ParsedValue) -> {CONV}::CResult<Self>"),
+            enum_from(&variants, &item.attrs)?,
+        ),
+        (Mode::To, Body::Struct(shape)) => (
+            "ToJson",
+            format!("fn to_json(&self) -> {JSON}::ParsedValue"),
+            struct_to(&shape, &item.attrs)?,
+        ),
+        (Mode::To, Body::Enum(variants)) => (
+            "ToJson",
+            format!("fn to_json(&self) -> {JSON}::ParsedValue"),
+            enum_to(&variants, &item.attrs)?,
+        ),
+    };
+    let generics = &item.generics;
+    let bounds = generics
+        .types
+        .iter()
+        .map(|name| format!("{name}: {CONV}::{trait_name},"))
+        .collect::<Vec<_>>();
+    let krate = item.attrs.krate.as_deref().unwrap_or("::parsing");
+    Ok(format!(
+        "const _: () = {{ use {krate} as {KRATE}; \
+         #[automatically_derived] impl<{}> {CONV}::{trait_name} for {}<{}> where {} {{ {signature} {{ {body} }} }} }};",
+        generics.params.join(", "),
+        item.name,
+        generics.args.join(", "),
+        bounds.concat(),
+    ))
+}
+
+fn expand(input: TokenStream, mode: Mode) -> TokenStream {
+    let code = parse_item(input)
+        .and_then(|item| generate(item, mode))
+        .unwrap_or_else(|err| format!("::core::compile_error!({err:?});"));
+    code.parse().unwrap_or_else(|_| {
+        "::core::compile_error!(\"json derive generated invalid code\");"
+            .parse()
+            .unwrap_or_default()
+    })
+}
+
+#[proc_macro_derive(FromJson, attributes(json))]
+pub fn derive_from_json(input: TokenStream) -> TokenStream {
+    expand(input, Mode::From)
+}
+
+#[proc_macro_derive(ToJson, attributes(json))]
+pub fn derive_to_json(input: TokenStream) -> TokenStream {
+    expand(input, Mode::To)
+}
+
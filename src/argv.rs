@@ -20,6 +20,19 @@ impl CurrentElt {
     fn is_empty(&self) -> bool {
         self.key.is_empty() || self.values.is_empty()
     }
+
+    /// Records the key even without values, so that flags can be detected,
+    /// and accumulates the values of a repeated key. The values before the
+    /// first key are only kept when there are some.
+    fn store(&self, res: &mut Argvs) {
+        if self.key == DEFAULT && self.values.is_empty() {
+            return;
+        }
+        res.inner
+            .entry(self.key.clone())
+            .or_default()
+            .extend(self.values.iter().cloned());
+    }
 }
 
 pub fn get_dash() -> SResult<(String, GlobalArgs)> {
@@ -55,40 +68,7 @@ pub trait GetExpected {
 
 impl GetExpected for &mut Local {
     fn get(&self) -> Argvs {
-        let mut args = env::args();
-        let path = args.next().unwrap_or_default();
-        let mut res = Argvs::default();
-        res.inner.insert("__file__".to_owned(), vec![path]);
-        let mut current = CurrentElt {
-            key: String::from("__default__"),
-            values: Vec::new(),
-        };
-        let mut left: Option<usize> = None;
-        for arg in args {
-            match (**self).get(&arg) {
-                Some(key) => {
-                    if !current.is_empty() {
-                        res.inner
-                            .insert(current.key.clone(), current.values.clone());
-                    }
-                    current = CurrentElt {
-                        key: key.0.clone(),
-                        values: Vec::new(),
-                    };
-                    left = key.1;
-                }
-                None if left.map_or_else(|| true, |nb| nb != 0_usize) => {
-                    current.values.push(arg);
-                    left.decr();
-                }
-                _ => (),
-            }
-        }
-        if !current.is_empty() {
-            res.inner
-                .insert(current.key.clone(), current.values.clone());
-        }
-        res
+        parse(self, env::args())
     }
 
     fn arg(self, args: Vec<&str>, nb: Option<usize>) -> Self {
@@ -103,6 +83,47 @@ impl GetExpected for &mut Local {
     }
 }
 
+/// Sorts `args`, the program path first, by the keys of `expected`.
+fn parse(expected: &Local, mut args: impl Iterator<Item = String>) -> Argvs {
+    let path = args.next().unwrap_or_default();
+    let mut res = Argvs::default();
+    res.inner.insert(FILE.to_owned(), vec![path]);
+    let mut current = CurrentElt {
+        key: String::from(DEFAULT),
+        values: Vec::new(),
+    };
+    let mut left: Option<usize> = None;
+    for arg in args {
+        // `--key=value` is the same as `--key value`.
+        let (arg, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with('-') && expected.contains_key(name) => {
+                (name.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg, None),
+        };
+        match expected.get(&arg) {
+            Some(key) => {
+                current.store(&mut res);
+                current = CurrentElt {
+                    key: key.0.clone(),
+                    values: inline.into_iter().collect(),
+                };
+                left = key.1;
+                if !current.values.is_empty() {
+                    left.decr();
+                }
+            }
+            None if left.map_or_else(|| true, |nb| nb != 0_usize) => {
+                current.values.push(arg);
+                left.decr();
+            }
+            _ => (),
+        }
+    }
+    current.store(&mut res);
+    res
+}
+
 trait OptionLen {
     fn decr(&mut self);
 }
@@ -118,6 +139,7 @@ impl OptionLen for Option<usize> {
 pub trait GetValue {
     fn get_one(&self, key: &str) -> SResult<String>;
     fn get_all(&self, key: &str) -> Option<&Vec<String>>;
+    fn has(&self, key: &str) -> bool;
 }
 
 impl GetValue for Argvs {
@@ -135,4 +157,40 @@ impl GetValue for Argvs {
     fn get_all(&self, key: &str) -> Option<&Vec<String>> {
         self.inner.get(key)
     }
+
+    fn has(&self, key: &str) -> bool {
+        self.inner.contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Argvs {
+        let mut expected = find();
+        let expected = (&mut expected)
+            .arg(vec!["-f", "--file"], Some(1))
+            .arg(vec!["--check"], Some(0))
+            .arg(vec!["-i"], None);
+        parse(expected, line.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let args = parse_line("prog -f a.json --file=b.json --check -i x y");
+        assert_eq!(args.get_all("-f"), Some(&vec!["a.json".to_owned(), "b.json".to_owned()]));
+        assert!(args.has("--check"));
+        assert_eq!(args.get_all("-i"), Some(&vec!["x".to_owned(), "y".to_owned()]));
+        assert_eq!(args.get_one(FILE).unwrap(), "prog");
+        assert!(args.get_one("-f").is_err());
+    }
+
+    #[test]
+    fn no_default_key_without_values() {
+        let args = parse_line("prog --check");
+        assert!(!args.has(DEFAULT));
+        assert_eq!(args.inner.len(), 2);
+        assert_eq!(parse_line("prog a --check").get_one(DEFAULT).unwrap(), "a");
+    }
 }
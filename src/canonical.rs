@@ -0,0 +1,174 @@
+use crate::errors::SResult;
+use crate::json::{self, ParsedValue, ParsedValueRef, Scalar};
+use crate::error;
+use core::fmt::Write as _;
+
+/// Formats a number like ECMAScript's `Number.prototype.toString`, as
+/// required by RFC 8785.
+pub fn es_number(val: f64) -> SResult<String> {
+    if !val.is_finite() {
+        return Err(error!("{val} cannot be represented in JSON."));
+    }
+    if val == 0.0_f64 {
+        return Ok(String::from("0"));
+    }
+    if val.is_sign_negative() {
+        return Ok(format!("-{}", es_number(-val)?));
+    }
+    // `{:e}` prints the shortest digits that round-trip, e.g. `1.2345e3`.
+    let sci = format!("{val:e}");
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits = mantissa.replace('.', "");
+    let exp = exp.parse::<i32>().map_err(|err| error!("{err}"))?;
+    let len = i32::try_from(digits.len()).unwrap_or(i32::MAX);
+    let point = exp.saturating_add(1);
+    let repeat = |count: i32| "0".repeat(usize::try_from(count).unwrap_or_default());
+    Ok(if len <= point && point <= 21 {
+        format!("{digits}{}", repeat(point.saturating_sub(len)))
+    } else if 0 < point && point <= 21 {
+        let (int, frac) = digits.split_at(usize::try_from(point).unwrap_or_default());
+        format!("{int}.{frac}")
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{digits}", repeat(-point))
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let sign = if exp < 0 { '-' } else { '+' };
+        if rest.is_empty() {
+            format!("{first}e{sign}{}", exp.abs())
+        } else {
+            format!("{first}.{rest}e{sign}{}", exp.abs())
+        }
+    })
+}
+
+/// Quotes a string with the minimal escaping of RFC 8785.
+pub fn es_string(val: &str) -> String {
+    let mut res = String::with_capacity(val.len().saturating_add(2));
+    res.push('"');
+    for ch in val.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\u{8}' => res.push_str("\\b"),
+            '\t' => res.push_str("\\t"),
+            '\n' => res.push_str("\\n"),
+            '\u{c}' => res.push_str("\\f"),
+            '\r' => res.push_str("\\r"),
+            _ if u32::from(ch) < 0x20 => {
+                let _ = write!(res, "\\u{:04x}", u32::from(ch));
+            }
+            _ => res.push(ch),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn write_value(value: &ParsedValue, res: &mut String) -> SResult<()> {
+    match json::to_ref(value) {
+        ParsedValueRef::Value(raw) => match json::to_scalar(raw) {
+            Scalar::Str(val) if raw.starts_with('"') => res.push_str(&es_string(&val)),
+            Scalar::Str(_) => return Err(error!("Invalid JSON literal {raw}.")),
+            Scalar::Null => res.push_str("null"),
+            Scalar::Bool(val) => res.push_str(if val { "true" } else { "false" }),
+            Scalar::Number(num) => res.push_str(&es_number(num)?),
+        },
+        ParsedValueRef::Array(arr) => {
+            res.push('[');
+            for (idx, elt) in arr.iter().enumerate() {
+                if idx != 0 {
+                    res.push(',');
+                }
+                write_value(elt, res)?;
+            }
+            res.push(']');
+        }
+        ParsedValueRef::Object(obj) => {
+            let mut items = obj
+                .iter()
+                .map(|item| (json::unescape(&item.key).encode_utf16().collect::<Vec<_>>(), &item.value))
+                .collect::<Vec<_>>();
+            items.sort_by(|left, right| left.0.cmp(&right.0));
+            if let Some(dup) = items.windows(2).find(|pair| pair.first().map(|fst| &fst.0) == pair.get(1).map(|snd| &snd.0)) {
+                let key = dup.first().map(|fst| String::from_utf16_lossy(&fst.0)).unwrap_or_default();
+                return Err(error!("Duplicate key {key:?} cannot be canonicalized."));
+            }
+            res.push('{');
+            for (idx, (key, elt)) in items.iter().enumerate() {
+                if idx != 0 {
+                    res.push(',');
+                }
+                res.push_str(&es_string(&String::from_utf16_lossy(key)));
+                res.push(':');
+                write_value(elt, res)?;
+            }
+            res.push('}');
+        }
+    }
+    Ok(())
+}
+
+/// Serializes a value with the JSON Canonicalization Scheme (RFC 8785).
+pub fn canonicalize(value: &ParsedValue) -> SResult<String> {
+    let mut res = String::new();
+    write_value(value, &mut res)?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn canonical(text: &str) -> String {
+        canonicalize(&json::parse(&mut string2parsechar("", text)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn numbers() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (5e-324, "5e-324"),
+            (0.000_001, "0.000001"),
+            (1e-7, "1e-7"),
+            (123.456, "123.456"),
+            (1.797_693_134_862_315_7e308, "1.7976931348623157e+308"),
+            (9_007_199_254_740_992.0, "9007199254740992"),
+            (333_333_333.333_333_3, "333333333.3333333"),
+        ];
+        for (val, expected) in cases {
+            assert_eq!(es_number(val).unwrap(), expected, "{val:e}");
+        }
+        assert!(es_number(f64::NAN).is_err());
+        assert!(es_number(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(es_string("a\"\\\u{8}\t\n\u{c}\r\u{1f}/é€😀"), "\"a\\\"\\\\\\b\\t\\n\\f\\r\\u001f/é€😀\"");
+    }
+
+    #[test]
+    fn keys_sort_by_utf16_code_units() {
+        // U+1F600 is encoded as D83D DE00, which sorts before U+FB33 although
+        // its code point is larger.
+        assert_eq!(
+            canonical(r#"{"\ufb33": 1, "😀": 2, "\u20ac": 3, "\r": 4, "1": 5, "\u0080": 6, "ö": 7}"#),
+            "{\"\\r\":4,\"1\":5,\"\u{80}\":6,\"ö\":7,\"€\":3,\"😀\":2,\"\u{fb33}\":1}"
+        );
+    }
+
+    #[test]
+    fn whitespace_and_number_formats_are_normalized() {
+        assert_eq!(
+            canonical("{ \"b\" : [ 1.0 , 1E2, -0 , 4.50, true, null ],\n \"a\" : \"x\" }"),
+            "{\"a\":\"x\",\"b\":[1,100,0,4.5,true,null]}"
+        );
+        assert!(canonicalize(&json::parse(&mut string2parsechar("", r#"{"a": 1, "\u0061": 2}"#)).unwrap()).is_err());
+    }
+}
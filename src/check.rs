@@ -0,0 +1,187 @@
+use crate::errors::get_code_color;
+
+/// Unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Shortest edit script between two lists of lines, by Myers' algorithm.
+fn edits<'text>(old: &[&'text str], new: &[&'text str]) -> Vec<(Edit, &'text str)> {
+    let max = old.len().saturating_add(new.len());
+    // Diagonal `x - y` is stored at `x - y + offset`, to stay unsigned.
+    let offset = max.saturating_add(1);
+    let at = |v: &[usize], diag: usize| v.get(diag).copied().unwrap_or_default();
+    let mut v = vec![0_usize; offset.saturating_mul(2).saturating_add(1)];
+    let mut trace = vec![];
+    'search: for depth in 0..=max {
+        trace.push(v.clone());
+        for diag in (offset.saturating_sub(depth)..=offset.saturating_add(depth)).step_by(2) {
+            let down = diag == offset.saturating_sub(depth)
+                || (diag != offset.saturating_add(depth)
+                    && at(&v, diag.saturating_sub(1)) < at(&v, diag.saturating_add(1)));
+            let mut x = if down {
+                at(&v, diag.saturating_add(1))
+            } else {
+                at(&v, diag.saturating_sub(1)).saturating_add(1)
+            };
+            let mut y = x.saturating_add(offset).saturating_sub(diag);
+            while x < old.len() && y < new.len() && old.get(x) == new.get(y) {
+                x = x.saturating_add(1);
+                y = y.saturating_add(1);
+            }
+            if let Some(slot) = v.get_mut(diag) {
+                *slot = x;
+            }
+            if x >= old.len() && y >= new.len() {
+                break 'search;
+            }
+        }
+    }
+    let (mut x, mut y) = (old.len(), new.len());
+    let mut res = vec![];
+    for (depth, v) in trace.iter().enumerate().rev() {
+        let diag = x.saturating_add(offset).saturating_sub(y);
+        let (prev_x, prev_y) = if depth == 0 {
+            (0, 0)
+        } else {
+            let down = diag == offset.saturating_sub(depth)
+                || (diag != offset.saturating_add(depth)
+                    && at(v, diag.saturating_sub(1)) < at(v, diag.saturating_add(1)));
+            let prev_diag = if down { diag.saturating_add(1) } else { diag.saturating_sub(1) };
+            let prev_x = at(v, prev_diag);
+            (prev_x, prev_x.saturating_add(offset).saturating_sub(prev_diag))
+        };
+        while x > prev_x && y > prev_y {
+            x = x.saturating_sub(1);
+            y = y.saturating_sub(1);
+            res.push((Edit::Keep, old.get(x).copied().unwrap_or_default()));
+        }
+        if depth > 0 {
+            if x == prev_x {
+                res.push((Edit::Insert, new.get(prev_y).copied().unwrap_or_default()));
+            } else {
+                res.push((Edit::Delete, old.get(prev_x).copied().unwrap_or_default()));
+            }
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    res.reverse();
+    res
+}
+
+/// `start,len` of a hunk side, `start` being the line before an empty side.
+fn range(before: usize, len: usize) -> String {
+    let start = if len == 0 { before } else { before.saturating_add(1) };
+    format!("{start},{len}")
+}
+
+/// Unified diff of two texts, empty when they are equal.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str, color: bool) -> String {
+    if old == new {
+        return String::new();
+    }
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("{}{line}\x1b[0m", get_code_color(code))
+        } else {
+            line
+        }
+    };
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = edits(&old_lines, &new_lines);
+    let changed = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (edit, _))| *edit != Edit::Keep)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    // Changes whose contexts touch share a hunk.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for idx in changed {
+        match hunks.last_mut() {
+            Some(hunk) if idx.saturating_sub(hunk.1) <= CONTEXT.saturating_mul(2).saturating_add(1) => hunk.1 = idx,
+            _ => hunks.push((idx, idx)),
+        }
+    }
+    let mut res = vec![paint("w", format!("--- {old_name}")), paint("w", format!("+++ {new_name}"))];
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT);
+        let end = last.saturating_add(CONTEXT).saturating_add(1).min(ops.len());
+        let count = |ops: &[(Edit, &str)], skip: Edit| ops.iter().filter(|(edit, _)| *edit != skip).count();
+        let (before, hunk) = (ops.get(..start).unwrap_or_default(), ops.get(start..end).unwrap_or_default());
+        res.push(paint(
+            "c",
+            format!(
+                "@@ -{} +{} @@",
+                range(count(before, Edit::Insert), count(hunk, Edit::Insert)),
+                range(count(before, Edit::Delete), count(hunk, Edit::Delete))
+            ),
+        ));
+        for (edit, line) in hunk {
+            let (sign, code) = match edit {
+                Edit::Keep => (" ", "w"),
+                Edit::Delete => ("-", "r"),
+                Edit::Insert => ("+", "g"),
+            };
+            res.push(paint(code, format!("{sign}{}", line.strip_suffix('\n').unwrap_or(line))));
+            if !line.ends_with('\n') {
+                res.push("\\ No newline at end of file".to_owned());
+            }
+        }
+    }
+    res.join("\n")
+}
+
+/// Outcome of checking one file.
+#[derive(Debug)]
+pub enum Status {
+    Formatted,
+    /// Unified diff from the file to its formatted form.
+    Unformatted(String),
+    Failed(String),
+}
+
+/// Diffs of the files that are not formatted, followed by a summary, and
+/// whether every file is formatted.
+pub fn report(results: &[(String, Status)]) -> (String, bool) {
+    let mut res = vec![];
+    let mut unformatted = vec![];
+    let mut failed = vec![];
+    for (path, status) in results {
+        match status {
+            Status::Formatted => (),
+            Status::Unformatted(diff) => {
+                res.push(diff.clone());
+                unformatted.push(path.as_str());
+            }
+            Status::Failed(err) => {
+                res.push(format!("{path}: {err}"));
+                failed.push(path.as_str());
+            }
+        }
+    }
+    let plural = |nb: usize| if nb == 1 { "" } else { "s" };
+    let mut summary = format!("{} file{} checked", results.len(), plural(results.len()));
+    if !unformatted.is_empty() {
+        summary.push_str(&format!(
+            ", {} would be reformatted: {}",
+            unformatted.len(),
+            unformatted.join(", ")
+        ));
+    }
+    if !failed.is_empty() {
+        summary.push_str(&format!(", {} could not be read: {}", failed.len(), failed.join(", ")));
+    }
+    if unformatted.is_empty() && failed.is_empty() {
+        summary.push_str(", all formatted");
+    }
+    summary.push('.');
+    res.push(summary);
+    (res.join("\n\n"), unformatted.is_empty() && failed.is_empty())
+}
@@ -0,0 +1,523 @@
+use crate::argv::GetExpected;
+use crate::argv::GetValue;
+use crate::errors::SResult;
+use crate::errors::ToError;
+use crate::{
+    argv, canonical, check, codegen, diff, error, files, filter, flatten, format, hash, json, patch, pointer, schema,
+    ser, sha256, sort, validate, view,
+};
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::process::ExitCode;
+
+/// Result of a mode: a document is serialized straight into the output.
+enum Output {
+    Text(String),
+    Document(json::ParsedValue),
+}
+
+/// Layout of `read`, from `--indent <n|tab>`, `--no-colon-space`,
+/// `--inline <width>`, `--final-newline`, `--line-ending <lf|crlf>` and
+/// `--minify`, and its escaping from `--ascii` and `--html-safe`. Files that
+/// `--check` and `--write` reformat keep their final newline regardless.
+fn format_options(args: &argv::Argvs) -> SResult<ser::FormatOptions> {
+    let mut opts = ser::FormatOptions::default();
+    if let Ok(indent) = args.get_one("--indent") {
+        opts.indent = match indent.as_str() {
+            "tab" | "tabs" => ser::Indent::Tabs,
+            width => ser::Indent::Spaces(
+                width
+                    .parse()
+                    .map_err(|_| error!("Indent must be a width or \"tab\", found {width:?}."))?,
+            ),
+        };
+    }
+    if let Ok(width) = args.get_one("--inline") {
+        opts.inline_width = width
+            .parse()
+            .map_err(|_| error!("Inline width must be a number, found {width:?}."))?;
+    }
+    if let Ok(ending) = args.get_one("--line-ending") {
+        opts.line_ending = match ending.as_str() {
+            "lf" => ser::LineEnding::Lf,
+            "crlf" => ser::LineEnding::CrLf,
+            _ => return Err(error!("Line ending must be lf or crlf, found {ending:?}.")),
+        };
+    }
+    opts.space_after_colon = !args.has("--no-colon-space");
+    opts.final_newline = args.has("--final-newline");
+    opts.minify = args.has("--minify");
+    opts.ascii = args.has("--ascii");
+    opts.html_safe = args.has("--html-safe");
+    Ok(opts)
+}
+
+/// Key ordering from `--sort-keys <alpha|natural>`, `--key-order <file>` and
+/// the repeatable `--sort-path <pointer>`.
+fn sort_options(formats: &format::Registry, args: &argv::Argvs) -> SResult<sort::SortOptions> {
+    let mut opts = sort::SortOptions::default();
+    if let Ok(name) = args.get_one("--sort-keys") {
+        opts.order = Some(sort::Order::from_name(&name)?);
+    }
+    if let Ok(path) = args.get_one("--key-order") {
+        opts.load_custom(&parse_file(formats, None, &path)?)?;
+    }
+    for path in args.get_all("--sort-path").into_iter().flatten() {
+        opts.paths.push(pointer::parse(path)?);
+    }
+    Ok(opts)
+}
+
+fn parse_file(formats: &format::Registry, from: Option<&str>, path: &str) -> SResult<json::ParsedValue> {
+    formats.read(from, path, &files::load(path)?)
+}
+
+/// Records of all the files, taken as samples of one type: one document per
+/// file, or one per line of an NDJSON file.
+fn samples(
+    formats: &format::Registry,
+    from: Option<&str>,
+    files: Option<&Vec<String>>,
+) -> SResult<Vec<json::ParsedValue>> {
+    let files = files
+        .filter(|files| !files.is_empty())
+        .ok_or_else(|| error!("Expected at least one sample file."))?;
+    let mut samples = vec![];
+    for file in files {
+        let content = files::load(file)?;
+        samples.extend(formats.records(from, file, &content)?);
+    }
+    Ok(samples)
+}
+
+/// Returns the rendered differences and whether the documents are the same.
+fn diff(
+    formats: &format::Registry,
+    from: Option<&str>,
+    files: Option<&Vec<String>>,
+    as_patch: bool,
+    opts: &diff::Options,
+    color: bool,
+) -> SResult<(String, bool)> {
+    let Some([old, new]) = files.map(Vec::as_slice) else {
+        return Err(error!("Diff expects two files: -f old.json -f new.json."));
+    };
+    let (old, new) = (parse_file(formats, from, old)?, parse_file(formats, from, new)?);
+    if as_patch {
+        let patch = patch::diff(&old, &new);
+        let same = matches!(&patch, json::ParsedValue::Array(ops) if ops.is_empty());
+        Ok((json::stringify(&patch)?, same))
+    } else {
+        let changes = diff::compare(&old, &new, opts);
+        Ok((diff::render(&changes, color), changes.is_empty()))
+    }
+}
+
+/// Formats every file in memory and compares it with its bytes, returning the
+/// diffs with a summary and whether every file is formatted.
+fn check(
+    formats: &format::Registry,
+    from: Option<&str>,
+    files: Option<&Vec<String>>,
+    opts: &ser::FormatOptions,
+    sorting: &sort::SortOptions,
+    color: bool,
+) -> SResult<(String, bool)> {
+    let files = files
+        .filter(|files| !files.is_empty())
+        .ok_or_else(|| error!("Check expects at least one file."))?;
+    let results = files
+        .iter()
+        .map(|file| {
+            let formatted = files::load(file).and_then(|content| {
+                let format = formats.detect(from, file, &content)?;
+                let mut value = formats.read(from, file, &content)?;
+                sort::sort_keys(&mut value, sorting);
+                Ok((format.serialize(&value, &opts.for_file(&content))?, content))
+            });
+            let status = match formatted {
+                Ok((formatted, content)) if formatted == content => check::Status::Formatted,
+                Ok((formatted, content)) => check::Status::Unformatted(check::unified(
+                    &content,
+                    &formatted,
+                    files::display(file),
+                    &format!("{} (formatted)", files::display(file)),
+                    color,
+                )),
+                Err(err) => check::Status::Failed(err),
+            };
+            (file.clone(), status)
+        })
+        .collect::<Vec<_>>();
+    Ok(check::report(&results))
+}
+
+/// Prints the content digest of every file, or of every subtree of every file.
+fn hash(
+    formats: &format::Registry,
+    from: Option<&str>,
+    files: Option<&Vec<String>>,
+    subtrees: bool,
+) -> SResult<String> {
+    let files = files
+        .filter(|files| !files.is_empty())
+        .ok_or_else(|| error!("Hash expects at least one file."))?;
+    let mut lines = vec![];
+    for file in files {
+        let value = parse_file(formats, from, file)?;
+        if subtrees {
+            for (path, digest) in hash::subtrees(&value) {
+                lines.push(format!("{}  {file}:{path}", sha256::hex(&digest)));
+            }
+        } else {
+            lines.push(format!("{}  {file}", sha256::hex(&hash::digest(&value))));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Width of the table view: `--width`, else the `COLUMNS` of the terminal,
+/// else 80.
+fn terminal_width(args: &argv::Argvs) -> SResult<usize> {
+    match args.get_one("--width") {
+        Ok(width) => width
+            .parse()
+            .map_err(|_| error!("Width must be a number, found {width:?}.")),
+        Err(_) => Ok(env::var("COLUMNS")
+            .ok()
+            .and_then(|width| width.trim().parse().ok())
+            .unwrap_or(80)),
+    }
+}
+
+/// Writes the result of a mode, sorting documents first.
+fn emit(
+    out: Output,
+    opts: &ser::FormatOptions,
+    sorting: &sort::SortOptions,
+    output: &str,
+    target: &dyn format::Format,
+) -> SResult<()> {
+    if matches!(out, Output::Text(_)) && target.name() != "json" {
+        return Err(error!(
+            "This mode prints text and cannot write {}, drop --to or use another output extension.",
+            target.name()
+        ));
+    }
+    match (out, output) {
+        // The same bytes as with `-o`, a newline only keeping the prompt apart
+        // on a terminal.
+        (Output::Text(content), "std" | "stdout" | "out" | "") => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(content.as_bytes()).cast_error()?;
+            if stdout.is_terminal() && !content.ends_with('\n') {
+                writeln!(stdout).cast_error()?;
+            }
+        }
+        (Output::Text(content), file) => fs::write(file, content).cast_error()?,
+        (Output::Document(mut value), _) => {
+            if !sorting.is_empty() {
+                sort::sort_keys(&mut value, sorting);
+            }
+            write_document(&value, opts, output, target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a file by its formatted document, in the format of the file and
+/// only touching it if the content changes.
+fn rewrite(
+    formats: &format::Registry,
+    from: Option<&str>,
+    file: &str,
+    out: Output,
+    opts: &ser::FormatOptions,
+    sorting: &sort::SortOptions,
+) -> SResult<&'static str> {
+    let Output::Document(mut value) = out else {
+        return Err(error!("This mode prints text, which cannot replace the file."));
+    };
+    if file == files::STDIN {
+        return Err(error!("Standard input cannot be written in place."));
+    }
+    sort::sort_keys(&mut value, sorting);
+    let original = files::load(file)?;
+    let formatted = formats
+        .detect(from, file, &original)?
+        .serialize(&value, &opts.for_file(&original))?;
+    if formatted == original {
+        return Ok("unchanged");
+    }
+    files::write_atomic(file, &formatted)?;
+    Ok("reformatted")
+}
+
+/// Writes a document into the output file, or stdout.
+fn write_document(
+    value: &json::ParsedValue,
+    opts: &ser::FormatOptions,
+    output: &str,
+    target: &dyn format::Format,
+) -> SResult<()> {
+    if matches!(output, "std" | "stdout" | "out" | "") {
+        let mut stdout = io::stdout().lock();
+        target.write(value, opts, &mut stdout)?;
+        if !opts.final_newline {
+            writeln!(stdout).cast_error()?;
+        }
+        return Ok(());
+    }
+    let mut writer = io::BufWriter::new(fs::File::create(output).cast_error()?);
+    target.write(value, opts, &mut writer)?;
+    writer.flush().cast_error()
+}
+
+fn main_wrapper(formats: &format::Registry) -> SResult<ExitCode> {
+    let args = argv::find()
+        // .arg(vec![""], None)
+        .arg(vec!["-f", "--file"], Some(1))
+        .arg(vec!["-t", "--type"], Some(1))
+        .arg(vec!["-o", "--output"], Some(1))
+        .arg(vec!["-v", "--value"], Some(1))
+        .arg(vec!["-e", "--expression"], Some(1))
+        .arg(vec!["-P", "--patch"], Some(1))
+        .arg(vec!["-s", "--schema"], Some(1))
+        .arg(vec!["--as-patch"], Some(0))
+        .arg(vec!["-m", "--merge"], Some(1))
+        .arg(vec!["-i", "--ignore"], Some(1))
+        .arg(vec!["--ignore-key-order"], Some(0))
+        .arg(vec!["--ignore-array-order"], Some(0))
+        .arg(vec!["--subtrees"], Some(0))
+        .arg(vec!["--name"], Some(1))
+        .arg(vec!["--tag"], Some(1))
+        .arg(vec!["--separator"], Some(1))
+        .arg(vec!["--array-notation"], Some(1))
+        .arg(vec!["--pointer"], Some(0))
+        .arg(vec!["--indent"], Some(1))
+        .arg(vec!["--no-colon-space"], Some(0))
+        .arg(vec!["--inline"], Some(1))
+        .arg(vec!["--final-newline"], Some(0))
+        .arg(vec!["--line-ending"], Some(1))
+        .arg(vec!["--minify"], Some(0))
+        .arg(vec!["--ascii"], Some(0))
+        .arg(vec!["--html-safe"], Some(0))
+        .arg(vec!["--sort-keys"], Some(1))
+        .arg(vec!["--key-order"], Some(1))
+        .arg(vec!["--sort-path"], Some(1))
+        .arg(vec!["--color"], Some(1))
+        .arg(vec!["--max-string"], Some(1))
+        .arg(vec!["--max-items"], Some(1))
+        .arg(vec!["--width"], Some(1))
+        .arg(vec!["--check"], Some(0))
+        .arg(vec!["--write"], Some(0))
+        .arg(vec!["--include"], Some(1))
+        .arg(vec!["--exclude"], Some(1))
+        .arg(vec!["--from"], Some(1))
+        .arg(vec!["--to"], Some(1))
+        .get();
+    let paths = args
+        .get_all("-f")
+        .filter(|paths| !paths.is_empty())
+        .cloned()
+        .unwrap_or_else(|| vec!["./data/test.json".to_owned()]);
+    let selection = files::Selection {
+        include: args
+            .get_all("--include")
+            .cloned()
+            .unwrap_or_else(|| formats.globs()),
+        exclude: args.get_all("--exclude").cloned().unwrap_or_default(),
+    };
+    let paths = files::expand(&paths, &selection)?;
+    let output = args.get_one("-o").unwrap_or_default();
+    let stdout = matches!(output.as_str(), "std" | "stdout" | "out" | "");
+    let mut opts = format_options(&args)?;
+    // Resolved once, for the documents and for the colored diffs.
+    let color = match args.get_one("--color").as_deref().unwrap_or("auto") {
+        "always" => true,
+        "never" => false,
+        "auto" => stdout && io::stdout().is_terminal(),
+        other => return Err(error!("Color must be always, never or auto, found {other:?}.")),
+    };
+    opts.color = color;
+    let from = args.get_one("--from").ok();
+    let from = from.as_deref();
+    // `--to`, else the extension of `-o`, JSON being kept for stdout and
+    // unknown extensions.
+    let target = match args.get_one("--to") {
+        Ok(name) => formats.by_name(&name)?,
+        Err(_) => match formats.by_path(&output).filter(|_| !stdout) {
+            Some(target) => target,
+            None => formats.by_name("json")?,
+        },
+    };
+    // Only the JSON serializer highlights.
+    if target.name() != "json" {
+        opts.color = false;
+    }
+    let sorting = sort_options(formats, &args)?;
+    let mut status = ExitCode::SUCCESS;
+    let mode = args.get_one("-t")?;
+    // Modes reading all the files at once.
+    let all = match mode.as_str() {
+        "read" if args.has("--check") => Some(check(formats, from, Some(&paths), &opts, &sorting, color).map(|(content, formatted)| {
+            if !formatted {
+                status = ExitCode::FAILURE;
+            }
+            Output::Text(content)
+        })),
+        "hash" => Some(hash(formats, from, Some(&paths), args.has("--subtrees")).map(Output::Text)),
+        "infer-schema" => Some(samples(formats, from, Some(&paths)).map(|samples| Output::Document(schema::infer(&samples)))),
+        "codegen" => Some(samples(formats, from, Some(&paths)).map(|samples| {
+            let opts = codegen::Options {
+                name: args.get_one("--name").unwrap_or_else(|_| "Root".to_owned()),
+                tag: args.get_one("--tag").ok(),
+            };
+            Output::Text(codegen::generate(&samples, &opts))
+        })),
+        "diff" => {
+            let opts = diff::Options {
+                ignore_key_order: args.has("--ignore-key-order"),
+                ignore_array_order: args.has("--ignore-array-order"),
+                ignore: args.get_all("-i").cloned().unwrap_or_default(),
+            };
+            Some(diff(formats, from, Some(&paths), args.has("--as-patch"), &opts, color).map(|(content, same)| {
+                if !same {
+                    status = ExitCode::FAILURE;
+                }
+                Output::Text(content)
+            }))
+        }
+        _ => None,
+    };
+    if let Some(result) = all {
+        emit(result?, &opts, &sorting, &output, target)?;
+        return Ok(status);
+    }
+    if mode == "minify" {
+        opts.minify = true;
+    }
+    // Modes reading one file at a time.
+    let run = |file: &str, status: &mut ExitCode| -> SResult<Output> {
+        let content = files::load(file)?;
+        let value = formats.read(from, file, &content)?;
+        match mode.as_str() {
+            "read" | "minify" => Ok(Output::Document(value)),
+            "append" => json::append(
+                value,
+                &formats.read(None, "", &args.get_one("-v")?)?,
+                args.get_one("-m")
+                    .map_or_else(|_| Ok(json::Merge::default()), |name| json::Merge::from_name(&name))?,
+            )
+            .map(Output::Document),
+            "canonical" => canonical::canonicalize(&value).map(Output::Text),
+            mode @ ("flatten" | "unflatten") => {
+                let keys = if args.has("--pointer") {
+                    flatten::Keys::Pointer
+                } else {
+                    flatten::Keys::Path {
+                        separator: args.get_one("--separator").unwrap_or_else(|_| ".".to_owned()),
+                        arrays: args
+                            .get_one("--array-notation")
+                            .map_or_else(|_| Ok(flatten::Arrays::default()), |name| flatten::Arrays::from_name(&name))?,
+                    }
+                };
+                flatten::run(&value, &keys, mode == "unflatten").map(Output::Document)
+            }
+            "tree" => {
+                let mut opts = view::TreeOptions::default();
+                if let Ok(max) = args.get_one("--max-string") {
+                    opts.max_string = max
+                        .parse()
+                        .map_err(|_| error!("Max string length must be a number, found {max:?}."))?;
+                }
+                if let Ok(max) = args.get_one("--max-items") {
+                    opts.max_items = max
+                        .parse()
+                        .map_err(|_| error!("Max item count must be a number, found {max:?}."))?;
+                }
+                Ok(Output::Text(view::tree(&value, &opts)))
+            }
+            "table" => view::table(&value, terminal_width(&args)?).map(Output::Text),
+            "filter" => filter::run(&value, &args.get_one("-e")?).map(Output::Text),
+            "patch" => {
+                let patchname = args.get_one("-P")?;
+                let patch = files::load(&patchname)?;
+                patch::run(
+                    &value,
+                    &formats.read(None, &patchname, &patch)?,
+                    &formats.locate(None, &patchname, &patch)?,
+                )
+                .map(Output::Document)
+            }
+            "validate" => {
+                let schemaname = args.get_one("-s")?;
+                let positions = formats.locate(from, file, &content)?;
+                validate::run(&value, &positions, &parse_file(formats, None, &schemaname)?).map(
+                    |(content, valid)| {
+                        if !valid {
+                            *status = ExitCode::FAILURE;
+                        }
+                        Output::Text(content)
+                    },
+                )
+            }
+            _ => Err(error!("Type not supported.")),
+        }
+    };
+    let write = args.has("--write");
+    if let [file] = paths.as_slice() {
+        if !write {
+            emit(run(file, &mut status)?, &opts, &sorting, &output, target)?;
+            return Ok(status);
+        }
+    }
+    if write && !output.is_empty() {
+        return Err(error!("--write rewrites the files in place and cannot be used with -o."));
+    }
+    if !write && !stdout {
+        return Err(error!("-o takes a single input file, use --write to rewrite several files."));
+    }
+    let mut summary = vec![];
+    let mut failed = 0_usize;
+    for file in &paths {
+        let result = run(file, &mut status).and_then(|out| {
+            if write {
+                rewrite(formats, from, file, out, &opts, &sorting)
+            } else {
+                emit(out, &opts, &sorting, &output, target).map(|()| "done")
+            }
+        });
+        summary.push(match result {
+            Ok(label) => format!("{}: {label}", files::display(file)),
+            Err(err) => {
+                failed = failed.saturating_add(1);
+                format!("{}: {err}", files::display(file))
+            }
+        });
+    }
+    if failed > 0 {
+        status = ExitCode::FAILURE;
+    }
+    summary.push(format!("{} files processed, {failed} failed.", paths.len()));
+    #[allow(clippy::print_stderr)]
+    {
+        eprintln!("{}", summary.join("\n"));
+    }
+    Ok(status)
+}
+
+/// Runs the command line on the formats of `formats`, which a program
+/// embedding it can extend with [`format::Registry::register`].
+pub fn run(formats: &format::Registry) -> ExitCode {
+    #[allow(clippy::print_stdout)]
+    match main_wrapper(formats) {
+        Ok(status) => status,
+        Err(err) => {
+            println!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
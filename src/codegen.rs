@@ -0,0 +1,346 @@
+use crate::convert::member;
+use crate::json::{self, ParsedValue, Scalar};
+
+const DERIVES: &str = "#[derive(Debug, Clone, FromJson, ToJson)]";
+
+/// Keywords that cannot be raw identifiers.
+const RESERVED: [&str; 5] = ["self", "Self", "super", "crate", "_"];
+
+const KEYWORDS: [&str; 51] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for", "if",
+    "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait",
+    "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield", "union", "macro_rules", "raw",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Name of the root type.
+    pub name: String,
+    /// Field whose string value selects a variant: objects that all have it
+    /// become internally tagged enums rather than one merged struct.
+    pub tag: Option<String>,
+}
+
+struct Generator<'opts> {
+    opts: &'opts Options,
+    /// Definitions by type name, in order of creation.
+    defs: Vec<(String, String)>,
+}
+
+fn words(key: &str) -> Vec<String> {
+    let mut res = vec![];
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for ch in key.chars() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                res.push(current.clone());
+                current.clear();
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_uppercase() && prev_lower {
+            res.push(current.clone());
+            current.clear();
+        }
+        prev_lower = ch.is_lowercase() || ch.is_numeric();
+        current.extend(ch.to_lowercase());
+    }
+    if !current.is_empty() {
+        res.push(current);
+    }
+    res
+}
+
+fn pascal_case(key: &str) -> String {
+    let name = words(key)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    match name.chars().next() {
+        None => "Value".to_owned(),
+        Some(first) if first.is_numeric() => format!("Value{name}"),
+        Some(_) => name,
+    }
+}
+
+fn snake_case(key: &str) -> String {
+    let name = words(key).join("_");
+    match name.chars().next() {
+        None => "field".to_owned(),
+        Some(first) if first.is_numeric() => format!("field_{name}"),
+        Some(_) if RESERVED.contains(&name.as_str()) => format!("{name}_"),
+        Some(_) if KEYWORDS.contains(&name.as_str()) => format!("r#{name}"),
+        Some(_) => name,
+    }
+}
+
+/// Type name for the elements of an array named `name`.
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if name.ends_with("ss") {
+        format!("{name}Item")
+    } else if let Some(stem) = name.strip_suffix('s').filter(|stem| !stem.is_empty()) {
+        stem.to_owned()
+    } else {
+        format!("{name}Item")
+    }
+}
+
+/// `#[json(rename = "...")]` line when the identifier does not spell the key.
+fn rename(ident: &str, key: &str, indent: &str) -> String {
+    if ident.strip_prefix("r#").unwrap_or(ident) == key {
+        String::new()
+    } else {
+        format!("{indent}#[json(rename = {key:?})]\n")
+    }
+}
+
+impl Generator<'_> {
+    /// Registers a definition, reusing an identical one and numbering the
+    /// name when a different type already has it.
+    fn define(&mut self, name: &str, body: impl Fn(&str) -> String) -> String {
+        let mut candidate = name.to_owned();
+        let mut idx = 1_usize;
+        loop {
+            let text = body(&candidate);
+            match self.defs.iter().find(|def| def.0 == candidate) {
+                Some(def) if def.1 == text => return candidate,
+                Some(_) => {
+                    idx = idx.saturating_add(1);
+                    candidate = format!("{name}{idx}");
+                }
+                None => {
+                    self.defs.push((candidate.clone(), text));
+                    return candidate;
+                }
+            }
+        }
+    }
+
+    /// Fields of the merged objects in order of first appearance, optional
+    /// when missing from some of them. Their types merge the values of `all`,
+    /// so that the variants of an enum share the types of their fields.
+    fn fields(&mut self, objects: &[&json::Parsed], all: &[&json::Parsed], skip: Option<&str>, vis: &str) -> String {
+        let indent = if vis.is_empty() { "        " } else { "    " };
+        let mut keys: Vec<String> = vec![];
+        for obj in objects {
+            for item in obj.iter() {
+                let key = json::unescape(&item.key);
+                if Some(key.as_str()) != skip && !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        let mut res = String::new();
+        for key in keys {
+            let values = all.iter().filter_map(|obj| member(obj, &key)).collect::<Vec<_>>();
+            let present = objects.iter().filter(|obj| member(obj, &key).is_some()).count();
+            let mut ty = self.type_of(&values, &pascal_case(&key));
+            if present < objects.len() && !ty.starts_with("Option<") {
+                ty = format!("Option<{ty}>");
+            }
+            let ident = snake_case(&key);
+            res.push_str(&format!("{}{indent}{vis}{ident}: {ty},\n", rename(&ident, &key, indent)));
+        }
+        res
+    }
+
+    fn object_type(&mut self, objects: &[&json::Parsed], name: &str) -> String {
+        let tag = self.opts.tag.clone();
+        let tags = tag
+            .as_ref()
+            .map(|tag| {
+                objects
+                    .iter()
+                    .map(|obj| match member(obj, tag).and_then(ParsedValue::scalar) {
+                        Some(Scalar::Str(val)) => Some(val),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .unwrap_or_default();
+        let (Some(tag), Some(tags)) = (tag, tags) else {
+            let fields = self.fields(objects, objects, None, "pub ");
+            return self.define(name, |candidate| {
+                format!("{DERIVES}\npub struct {candidate} {{\n{fields}}}")
+            });
+        };
+        let mut variants: Vec<String> = vec![];
+        for val in &tags {
+            if !variants.contains(val) {
+                variants.push(val.clone());
+            }
+        }
+        let mut body = String::new();
+        for val in variants {
+            let members = objects
+                .iter()
+                .zip(&tags)
+                .filter(|(_, found)| **found == val)
+                .map(|(obj, _)| *obj)
+                .collect::<Vec<_>>();
+            let ident = pascal_case(&val);
+            body.push_str(&rename(&ident, &val, "    "));
+            let fields = self.fields(&members, objects, Some(&tag), "");
+            if fields.is_empty() {
+                body.push_str(&format!("    {ident},\n"));
+            } else {
+                body.push_str(&format!("    {ident} {{\n{fields}    }},\n"));
+            }
+        }
+        self.define(name, |candidate| {
+            format!("{DERIVES}\n#[json(tag = {tag:?})]\npub enum {candidate} {{\n{body}}}")
+        })
+    }
+
+    /// Rust type of the values seen at one place, defining the structs and
+    /// enums it needs.
+    fn type_of(&mut self, values: &[&ParsedValue], name: &str) -> String {
+        let mut kinds = vec![];
+        let mut nullable = false;
+        let mut arrays = vec![];
+        let mut objects = vec![];
+        for value in values {
+            let kind = match value {
+                ParsedValue::Array(arr) => {
+                    arrays.extend(arr);
+                    "array"
+                }
+                ParsedValue::Object(obj) => {
+                    objects.push(obj);
+                    "object"
+                }
+                ParsedValue::Value(raw) => match json::to_scalar(raw) {
+                    Scalar::Null => {
+                        nullable = true;
+                        continue;
+                    }
+                    Scalar::Bool(_) => "bool",
+                    Scalar::Number(_) if raw.contains(['.', 'e', 'E']) => "f64",
+                    Scalar::Number(_) => "i64",
+                    Scalar::Str(_) => "String",
+                },
+            };
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        if kinds.len() == 2 && kinds.contains(&"i64") && kinds.contains(&"f64") {
+            kinds.retain(|kind| *kind == "f64");
+        }
+        let ty = match kinds.as_slice() {
+            ["array"] if arrays.is_empty() => "Vec<ParsedValue>".to_owned(),
+            ["array"] => format!("Vec<{}>", self.type_of(&arrays, &singular(name))),
+            ["object"] => self.object_type(&objects, name),
+            [scalar] => (*scalar).to_owned(),
+            _ => "ParsedValue".to_owned(),
+        };
+        if nullable && ty != "ParsedValue" {
+            format!("Option<{ty}>")
+        } else {
+            ty
+        }
+    }
+}
+
+/// Rust definitions that the samples can be read into with `FromJson`, the
+/// root type first.
+pub fn generate(samples: &[ParsedValue], opts: &Options) -> String {
+    let mut generator = Generator { opts, defs: vec![] };
+    let root = generator.type_of(&samples.iter().collect::<Vec<_>>(), &opts.name);
+    let mut res = vec!["use parsing::convert::{FromJson, ToJson};\nuse parsing::json::ParsedValue;".to_owned()];
+    if !generator.defs.iter().any(|def| def.0 == root) {
+        res.push(format!("pub type {} = {root};", opts.name));
+    }
+    res.extend(generator.defs.into_iter().rev().map(|def| def.1));
+    res.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn generated(samples: &[&str], tag: Option<&str>) -> String {
+        let samples = samples
+            .iter()
+            .map(|text| json::parse(&mut string2parsechar("", text)).unwrap())
+            .collect::<Vec<_>>();
+        let opts = Options {
+            name: "Root".to_owned(),
+            tag: tag.map(str::to_owned),
+        };
+        generate(&samples, &opts)
+    }
+
+    const HEADER: &str = "use parsing::convert::{FromJson, ToJson};\nuse parsing::json::ParsedValue;\n\n";
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(pascal_case("zip-code"), "ZipCode");
+        assert_eq!(pascal_case("userID"), "UserId");
+        assert_eq!(pascal_case("2fa"), "Value2fa");
+        assert_eq!(pascal_case("--"), "Value");
+        assert_eq!(snake_case("userName"), "user_name");
+        assert_eq!(snake_case("type"), "r#type");
+        assert_eq!(snake_case("self"), "self_");
+        assert_eq!(snake_case("1st"), "field_1st");
+        assert_eq!(singular("Entries"), "Entry");
+        assert_eq!(singular("Address"), "AddressItem");
+        assert_eq!(singular("Tags"), "Tag");
+        assert_eq!(singular("Data"), "DataItem");
+    }
+
+    #[test]
+    fn structs() {
+        let samples = [
+            r#"{"userName": "a", "type": 1, "tags": ["x"], "items": [{"zip-code": 1, "city": "b"}], "extra": null}"#,
+            r#"{"userName": "b", "type": 2, "tags": [], "items": [{"zip-code": 1.5}]}"#,
+        ];
+        assert_eq!(
+            generated(&samples, None),
+            format!(
+                "{HEADER}{DERIVES}\npub struct Root {{\n    #[json(rename = \"userName\")]\n    pub user_name: String,\n    pub r#type: i64,\n    pub tags: Vec<String>,\n    pub items: Vec<Item>,\n    pub extra: Option<ParsedValue>,\n}}\n\n{DERIVES}\npub struct Item {{\n    #[json(rename = \"zip-code\")]\n    pub zip_code: f64,\n    pub city: Option<String>,\n}}"
+            )
+        );
+    }
+
+    #[test]
+    fn tagged_enums() {
+        let samples = [r#"{"kind": "circle", "r": 1.5}"#, r#"{"kind": "rect", "w": 1, "h": null}"#, r#"{"kind": "Empty"}"#];
+        assert_eq!(
+            generated(&samples, Some("kind")),
+            format!(
+                "{HEADER}{DERIVES}\n#[json(tag = \"kind\")]\npub enum Root {{\n    #[json(rename = \"circle\")]\n    Circle {{\n        r: f64,\n    }},\n    #[json(rename = \"rect\")]\n    Rect {{\n        w: i64,\n        h: ParsedValue,\n    }},\n    Empty,\n}}"
+            )
+        );
+        // Objects without the tag are merged into one struct.
+        assert!(generated(&[r#"{"kind": "a"}"#, r#"{"x": 1}"#], Some("kind")).contains("pub struct Root {"));
+    }
+
+    #[test]
+    fn same_names() {
+        let code = generated(&[r#"{"a": {"value": {"x": 1}}, "b": {"value": {"y": "z"}}}"#], None);
+        assert!(code.contains("pub struct Value {\n    pub x: i64,\n}"), "{code}");
+        assert!(code.contains("pub struct Value2 {\n    pub y: String,\n}"), "{code}");
+        assert!(code.contains("pub value: Value2,"), "{code}");
+        let code = generated(&[r#"{"a": {"value": {"x": 1}}, "b": {"value": {"x": 2}}}"#], None);
+        assert!(!code.contains("Value2"), "{code}");
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(generated(&["[1, 2.5]", "[]"], None), format!("{HEADER}pub type Root = Vec<f64>;"));
+        assert_eq!(generated(&["1", "\"a\""], None), format!("{HEADER}pub type Root = ParsedValue;"));
+    }
+}
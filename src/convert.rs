@@ -0,0 +1,460 @@
+use crate::errors::{SResult, ToError};
+use crate::json::{self, ParsedValue, Scalar};
+use crate::visit::{Path, PathElt};
+use core::fmt;
+use core::hash::BuildHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+pub use parsing_derive::{FromJson, ToJson};
+
+/// Conversion failure, with the JSON path of the value that could not be
+/// converted: `at /servers/2/port: expected integer, found string`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError {
+    pub path: Path,
+    pub msg: String,
+}
+
+impl ConvertError {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self {
+            path: Path::default(),
+            msg: msg.into(),
+        }
+    }
+
+    pub fn expected(expected: &str, found: &ParsedValue) -> Self {
+        Self::new(format!("expected {expected}, found {}", found.type_name()))
+    }
+
+    /// Prefixes the path with the element the failing value was found at, as
+    /// errors are built at the leaf and travel up to the root.
+    #[must_use]
+    pub fn at(mut self, elt: PathElt) -> Self {
+        self.path.0.insert(0, elt);
+        self
+    }
+
+    #[must_use]
+    pub fn at_key(self, key: &str) -> Self {
+        self.at(PathElt::Key(key.to_owned()))
+    }
+
+    #[must_use]
+    pub fn at_index(self, idx: usize) -> Self {
+        self.at(PathElt::Index(idx))
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.0.is_empty() {
+            write!(fmt, "at (root): {}", self.msg)
+        } else {
+            write!(fmt, "at {}: {}", self.path, self.msg)
+        }
+    }
+}
+
+pub type CResult<T> = Result<T, ConvertError>;
+
+pub trait FromJson: Sized {
+    fn from_json(value: &ParsedValue) -> CResult<Self>;
+}
+
+pub trait ToJson {
+    fn to_json(&self) -> ParsedValue;
+}
+
+/// Reads, parses and converts a whole file.
+pub fn load<T: FromJson>(path: &str) -> SResult<T> {
+    let content = fs::read_to_string(path).cast_error()?;
+    let value = json::parse(&mut crate::string2parsechar(path, &content))?;
+    T::from_json(&value).map_err(|err| crate::error!("{path}: {err}"))
+}
+
+impl FromJson for ParsedValue {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl ToJson for ParsedValue {
+    fn to_json(&self) -> ParsedValue {
+        self.clone()
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        match value.scalar() {
+            Some(Scalar::Bool(val)) => Ok(val),
+            _ => Err(ConvertError::expected("boolean", value)),
+        }
+    }
+}
+
+impl FromJson for String {
+    /// Only quoted strings, as `to_scalar` also reads bare tokens as strings.
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        match value {
+            ParsedValue::Value(raw) if raw.starts_with('"') => Ok(json::unescape(
+                raw.strip_prefix('"')
+                    .and_then(|val| val.strip_suffix('"'))
+                    .unwrap_or(raw),
+            )),
+            ParsedValue::Value(raw) if matches!(json::to_scalar(raw), Scalar::Str(_)) => {
+                Err(ConvertError::new(format!("expected string, found the unquoted token {raw}")))
+            }
+            _ => Err(ConvertError::expected("string", value)),
+        }
+    }
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {
+        $(impl FromJson for $t {
+            fn from_json(value: &ParsedValue) -> CResult<Self> {
+                let ParsedValue::Value(raw) = value else {
+                    return Err(ConvertError::expected("integer", value));
+                };
+                if let Ok(val) = raw.parse::<$t>() {
+                    return Ok(val);
+                }
+                match value.scalar() {
+                    Some(Scalar::Number(num)) if num.fract() == 0.0_f64 => format!("{num:.0}")
+                        .parse::<$t>()
+                        .map_err(|_| ConvertError::new(format!("{raw} is out of range for {}", stringify!($t)))),
+                    Some(Scalar::Number(_)) => Err(ConvertError::new(format!("expected integer, found {raw}"))),
+                    _ => Err(ConvertError::expected("integer", value)),
+                }
+            }
+        })*
+    };
+}
+
+integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromJson for f64 {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        match value.scalar() {
+            Some(Scalar::Number(num)) => Ok(num),
+            _ => Err(ConvertError::expected("number", value)),
+        }
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        #[allow(clippy::cast_possible_truncation, clippy::as_conversions)]
+        f64::from_json(value).map(|num| num as Self)
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::boolean(*self)
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::string(self)
+    }
+}
+
+macro_rules! to_json_integer {
+    ($($t:ty),*) => {
+        $(impl ToJson for $t {
+            fn to_json(&self) -> ParsedValue {
+                ParsedValue::Value(self.to_string())
+            }
+        })*
+    };
+}
+
+to_json_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl ToJson for f32 {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::number(f64::from(*self))
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::number(*self)
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::string(self)
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> ParsedValue {
+        (**self).to_json()
+    }
+}
+
+impl<T: FromJson> FromJson for Box<T> {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        T::from_json(value).map(Self::new)
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn to_json(&self) -> ParsedValue {
+        (**self).to_json()
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        match value.scalar() {
+            Some(Scalar::Null) => Ok(None),
+            _ => T::from_json(value).map(Some),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> ParsedValue {
+        self.as_ref().map_or_else(ParsedValue::null, ToJson::to_json)
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        let ParsedValue::Array(arr) = value else {
+            return Err(ConvertError::expected("array", value));
+        };
+        arr.iter()
+            .enumerate()
+            .map(|(idx, elt)| T::from_json(elt).map_err(|err| err.at_index(idx)))
+            .collect()
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> ParsedValue {
+        ParsedValue::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> ParsedValue {
+        self.as_slice().to_json()
+    }
+}
+
+impl<T: FromJson, const N: usize> FromJson for [T; N] {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        Vec::<T>::from_json(value)?
+            .try_into()
+            .map_err(|vec: Vec<T>| ConvertError::new(format!("expected an array of {N} elements, found {}", vec.len())))
+    }
+}
+
+impl<T: ToJson, const N: usize> ToJson for [T; N] {
+    fn to_json(&self) -> ParsedValue {
+        self.as_slice().to_json()
+    }
+}
+
+macro_rules! tuple {
+    ($len:literal => $($name:ident $idx:tt),*) => {
+        impl<$($name: FromJson),*> FromJson for ($($name,)*) {
+            fn from_json(value: &ParsedValue) -> CResult<Self> {
+                match value {
+                    ParsedValue::Array(arr) if arr.len() == $len => Ok(($(
+                        $name::from_json(arr.get($idx).ok_or_else(|| ConvertError::new("missing tuple element"))?)
+                            .map_err(|err| err.at_index($idx))?,
+                    )*)),
+                    ParsedValue::Array(arr) => Err(ConvertError::new(format!(
+                        "expected an array of {} elements, found {}",
+                        $len,
+                        arr.len()
+                    ))),
+                    _ => Err(ConvertError::expected("array", value)),
+                }
+            }
+        }
+
+        impl<$($name: ToJson),*> ToJson for ($($name,)*) {
+            fn to_json(&self) -> ParsedValue {
+                ParsedValue::Array(vec![$(self.$idx.to_json()),*])
+            }
+        }
+    };
+}
+
+tuple!(1 => A 0);
+tuple!(2 => A 0, B 1);
+tuple!(3 => A 0, B 1, C 2);
+tuple!(4 => A 0, B 1, C 2, D 3);
+tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+
+fn from_object<T: FromJson>(value: &ParsedValue) -> CResult<Vec<(String, T)>> {
+    let ParsedValue::Object(obj) = value else {
+        return Err(ConvertError::expected("object", value));
+    };
+    obj.iter()
+        .map(|item| {
+            let key = json::unescape(&item.key);
+            T::from_json(&item.value)
+                .map(|val| (key.clone(), val))
+                .map_err(|err| err.at_key(&key))
+        })
+        .collect()
+}
+
+fn to_object<'map, T: ToJson + 'map>(items: impl Iterator<Item = (&'map String, &'map T)>) -> ParsedValue {
+    ParsedValue::Object(
+        items
+            .map(|(key, val)| json::ParsingItem {
+                key: json::escape(key),
+                value: val.to_json(),
+            })
+            .collect(),
+    )
+}
+
+impl<T: FromJson, S: BuildHasher + Default> FromJson for HashMap<String, T, S> {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        from_object(value).map(|items| items.into_iter().collect())
+    }
+}
+
+impl<T: ToJson, S: BuildHasher> ToJson for HashMap<String, T, S> {
+    /// Keys are sorted, as the iteration order of a `HashMap` is not stable.
+    fn to_json(&self) -> ParsedValue {
+        let mut items = self.iter().collect::<Vec<_>>();
+        items.sort_by(|left, right| left.0.cmp(right.0));
+        to_object(items.into_iter())
+    }
+}
+
+impl<T: FromJson> FromJson for BTreeMap<String, T> {
+    fn from_json(value: &ParsedValue) -> CResult<Self> {
+        from_object(value).map(|items| items.into_iter().collect())
+    }
+}
+
+impl<T: ToJson> ToJson for BTreeMap<String, T> {
+    fn to_json(&self) -> ParsedValue {
+        to_object(self.iter())
+    }
+}
+
+// Support functions for the code generated by `parsing_derive`, kept here so
+// that the expansions stay short.
+
+pub fn object(value: &ParsedValue) -> CResult<&json::Parsed> {
+    match value {
+        ParsedValue::Object(obj) => Ok(obj),
+        _ => Err(ConvertError::expected("object", value)),
+    }
+}
+
+pub fn array(value: &ParsedValue, len: usize) -> CResult<&[ParsedValue]> {
+    match value {
+        ParsedValue::Array(arr) if arr.len() == len => Ok(arr),
+        ParsedValue::Array(arr) => Err(ConvertError::new(format!(
+            "expected an array of {len} elements, found {}",
+            arr.len()
+        ))),
+        _ => Err(ConvertError::expected("array", value)),
+    }
+}
+
+pub fn element<T: FromJson>(arr: &[ParsedValue], idx: usize) -> CResult<T> {
+    let elt = arr
+        .get(idx)
+        .ok_or_else(|| ConvertError::new(format!("missing element {idx}")))?;
+    T::from_json(elt).map_err(|err| err.at_index(idx))
+}
+
+pub fn member<'obj>(obj: &'obj json::Parsed, key: &str) -> Option<&'obj ParsedValue> {
+    crate::pointer::find_key(obj, key)
+        .and_then(|idx| obj.get(idx))
+        .map(|item| &item.value)
+}
+
+/// A missing field is read as null, so that `Option` fields may be omitted.
+pub fn field<T: FromJson>(obj: &json::Parsed, key: &str) -> CResult<T> {
+    match member(obj, key) {
+        Some(value) => T::from_json(value).map_err(|err| err.at_key(key)),
+        None => T::from_json(&ParsedValue::null()).map_err(|_| ConvertError::new(format!("missing field `{key}`"))),
+    }
+}
+
+pub fn field_or_default<T: FromJson + Default>(obj: &json::Parsed, key: &str) -> CResult<T> {
+    match member(obj, key) {
+        Some(value) => T::from_json(value).map_err(|err| err.at_key(key)),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn content<'val>(content: Option<&'val ParsedValue>, variant: &str) -> CResult<&'val ParsedValue> {
+    content.ok_or_else(|| ConvertError::new(format!("missing content for variant `{variant}`")))
+}
+
+/// Splits an externally tagged enum: `"Unit"` or `{"Variant": content}`.
+pub fn external(value: &ParsedValue) -> CResult<(String, Option<&ParsedValue>)> {
+    match (value, value.scalar()) {
+        (_, Some(Scalar::Str(name))) => Ok((name, None)),
+        (ParsedValue::Object(obj), _) => match obj.as_slice() {
+            [item] => Ok((json::unescape(&item.key), Some(&item.value))),
+            _ => Err(ConvertError::new(format!(
+                "expected an object with a single variant key, found {} keys",
+                obj.len()
+            ))),
+        },
+        _ => Err(ConvertError::expected("string or object", value)),
+    }
+}
+
+pub fn unknown_variant(found: &str, expected: &[&str]) -> ConvertError {
+    ConvertError::new(format!(
+        "unknown variant `{found}`, expected one of {}",
+        expected.join(", ")
+    ))
+}
+
+pub fn push_field(obj: &mut json::Parsed, key: &str, value: ParsedValue) {
+    obj.push(json::ParsingItem {
+        key: json::escape(key),
+        value,
+    });
+}
+
+/// Moves the members of a flattened field into its parent.
+pub fn flatten_into(obj: &mut json::Parsed, value: ParsedValue) {
+    if let ParsedValue::Object(items) = value {
+        obj.extend(items);
+    }
+}
+
+pub fn single(key: &str, value: ParsedValue) -> ParsedValue {
+    let mut obj = vec![];
+    push_field(&mut obj, key, value);
+    ParsedValue::Object(obj)
+}
+
+/// Adds the tag of an internally tagged variant in front of its content. The
+/// derive rejects newtype variants whose content is known not to be an
+/// object; other such content is kept under `value`, but cannot be read back.
+pub fn tagged(tag: &str, name: &str, value: ParsedValue) -> ParsedValue {
+    let mut obj = vec![];
+    push_field(&mut obj, tag, ParsedValue::string(name));
+    match value {
+        ParsedValue::Object(items) => obj.extend(items),
+        other => push_field(&mut obj, "value", other),
+    }
+    ParsedValue::Object(obj)
+}
@@ -0,0 +1,234 @@
+use crate::errors::get_code_color;
+use crate::json::{self, ParsedValue, ParsedValueRef};
+use crate::pointer;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub ignore_key_order: bool,
+    pub ignore_array_order: bool,
+    /// JSON Pointers whose subtrees are not compared.
+    pub ignore: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Change {
+    Added(String, ParsedValue),
+    Removed(String, ParsedValue),
+    Changed(String, ParsedValue, ParsedValue),
+    Reordered(String, Vec<String>, Vec<String>),
+}
+
+impl Options {
+    fn ignored(&self, path: &str) -> bool {
+        self.ignore.iter().any(|ignored| {
+            path.strip_prefix(ignored.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+fn keys(obj: &json::Parsed) -> Vec<String> {
+    obj.iter().map(|item| json::unescape(&item.key)).collect()
+}
+
+fn compare_objects(old: &json::Parsed, new: &json::Parsed, path: &str, opts: &Options, res: &mut Vec<Change>) {
+    for item in old {
+        let key = json::unescape(&item.key);
+        let sub = pointer::push(path, &key);
+        if pointer::find_key(new, &key).is_none() && !opts.ignored(&sub) {
+            res.push(Change::Removed(sub, item.value.clone()));
+        }
+    }
+    for item in new {
+        let key = json::unescape(&item.key);
+        let sub = pointer::push(path, &key);
+        match pointer::find_key(old, &key).and_then(|idx| old.get(idx)) {
+            Some(prev) => compare_at(&prev.value, &item.value, &sub, opts, res),
+            None if !opts.ignored(&sub) => res.push(Change::Added(sub, item.value.clone())),
+            None => (),
+        }
+    }
+    if !opts.ignore_key_order {
+        let (old_keys, new_keys) = (keys(old), keys(new));
+        let common_old = old_keys.iter().filter(|key| new_keys.contains(key)).cloned().collect::<Vec<_>>();
+        let common_new = new_keys.iter().filter(|key| old_keys.contains(key)).cloned().collect::<Vec<_>>();
+        if common_old != common_new {
+            res.push(Change::Reordered(path.to_owned(), common_old, common_new));
+        }
+    }
+}
+
+fn compare_unordered(old: &[ParsedValue], new: &[ParsedValue], path: &str, opts: &Options, res: &mut Vec<Change>) {
+    let mut unmatched = new.iter().enumerate().collect::<Vec<_>>();
+    for (idx, value) in old.iter().enumerate() {
+        match unmatched.iter().position(|(_, other)| other.same(value)) {
+            Some(found) => {
+                unmatched.remove(found);
+            }
+            None => {
+                let sub = pointer::push(path, &idx.to_string());
+                if !opts.ignored(&sub) {
+                    res.push(Change::Removed(sub, value.clone()));
+                }
+            }
+        }
+    }
+    for (idx, value) in unmatched {
+        let sub = pointer::push(path, &idx.to_string());
+        if !opts.ignored(&sub) {
+            res.push(Change::Added(sub, value.clone()));
+        }
+    }
+}
+
+fn compare_at(old: &ParsedValue, new: &ParsedValue, path: &str, opts: &Options, res: &mut Vec<Change>) {
+    if opts.ignored(path) {
+        return;
+    }
+    match (old, new) {
+        (ParsedValue::Object(left), ParsedValue::Object(right)) => compare_objects(left, right, path, opts, res),
+        (ParsedValue::Array(left), ParsedValue::Array(right)) if opts.ignore_array_order => {
+            compare_unordered(left, right, path, opts, res);
+        }
+        (ParsedValue::Array(left), ParsedValue::Array(right)) => {
+            for (idx, (lft, rgt)) in left.iter().zip(right).enumerate() {
+                compare_at(lft, rgt, &pointer::push(path, &idx.to_string()), opts, res);
+            }
+            for (idx, value) in left.iter().enumerate().skip(right.len()) {
+                let sub = pointer::push(path, &idx.to_string());
+                if !opts.ignored(&sub) {
+                    res.push(Change::Removed(sub, value.clone()));
+                }
+            }
+            for (idx, value) in right.iter().enumerate().skip(left.len()) {
+                let sub = pointer::push(path, &idx.to_string());
+                if !opts.ignored(&sub) {
+                    res.push(Change::Added(sub, value.clone()));
+                }
+            }
+        }
+        _ if old.same(new) => (),
+        _ => res.push(Change::Changed(path.to_owned(), old.clone(), new.clone())),
+    }
+}
+
+/// Lists the paths that differ between two documents.
+pub fn compare(old: &ParsedValue, new: &ParsedValue, opts: &Options) -> Vec<Change> {
+    let mut res = vec![];
+    compare_at(old, new, "", opts, &mut res);
+    res
+}
+
+/// Renders a value on a single line.
+fn inline(value: &ParsedValue) -> String {
+    match json::to_ref(value) {
+        ParsedValueRef::Value(val) => val.clone(),
+        ParsedValueRef::Array(arr) => format!("[{}]", arr.iter().map(inline).collect::<Vec<_>>().join(", ")),
+        ParsedValueRef::Object(obj) => format!(
+            "{{{}}}",
+            obj.iter()
+                .map(|item| format!("\"{}\": {}", item.key, inline(&item.value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn display(path: &str) -> &str {
+    if path.is_empty() {
+        "(root)"
+    } else {
+        path
+    }
+}
+
+pub fn render(changes: &[Change], color: bool) -> String {
+    changes
+        .iter()
+        .map(|change| {
+            let (code, line) = match change {
+                Change::Added(path, new) => ("g", format!("+ {}: {}", display(path), inline(new))),
+                Change::Removed(path, old) => ("r", format!("- {}: {}", display(path), inline(old))),
+                Change::Changed(path, old, new) => (
+                    "y",
+                    format!("~ {}: {} -> {}", display(path), inline(old), inline(new)),
+                ),
+                Change::Reordered(path, old, new) => (
+                    "c",
+                    format!("~ {}: key order {:?} -> {:?}", display(path), old, new),
+                ),
+            };
+            if color {
+                format!("{}{line}\x1b[0m", get_code_color(code))
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn changes(old: &str, new: &str, opts: &Options) -> String {
+        render(&compare(&parse(old), &parse(new), opts), false)
+    }
+
+    #[test]
+    fn paths() {
+        let old = r#"{"a": 1, "b": {"c": [1, 2, 3]}, "d": "x"}"#;
+        let new = r#"{"a": 1.0, "b": {"c": [1, 5]}, "e/f": null, "d": "y"}"#;
+        assert_eq!(
+            changes(old, new, &Options { ignore_key_order: true, ..Options::default() }),
+            "~ /b/c/1: 2 -> 5\n- /b/c/2: 3\n+ /e~1f: null\n~ /d: \"x\" -> \"y\""
+        );
+        assert_eq!(changes("[1]", "{}", &Options::default()), "~ (root): [1] -> {}");
+        assert!(compare(&parse(old), &parse(old), &Options::default()).is_empty());
+    }
+
+    #[test]
+    fn key_order() {
+        let (old, new) = (r#"{"a": 1, "b": 2, "c": 3}"#, r#"{"b": 2, "a": 1, "d": 4}"#);
+        assert_eq!(
+            changes(old, new, &Options::default()),
+            "- /c: 3\n+ /d: 4\n~ (root): key order [\"a\", \"b\"] -> [\"b\", \"a\"]"
+        );
+        assert_eq!(changes(old, new, &Options { ignore_key_order: true, ..Options::default() }), "- /c: 3\n+ /d: 4");
+    }
+
+    #[test]
+    fn array_order() {
+        let opts = Options { ignore_array_order: true, ..Options::default() };
+        assert!(compare(&parse(r#"[1, {"a": [2]}, 1]"#), &parse(r#"[{"a": [2]}, 1, 1]"#), &opts).is_empty());
+        assert_eq!(changes("[1, 2, 2]", "[2, 3, 1]", &opts), "- /2: 2\n+ /1: 3");
+        assert_eq!(changes("[1, 2]", "[2, 1]", &Options::default()), "~ /0: 1 -> 2\n~ /1: 2 -> 1");
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let opts = Options {
+            ignore: vec!["/meta".to_owned(), "/list/1".to_owned()],
+            ..Options::default()
+        };
+        let old = r#"{"meta": {"at": 1}, "metadata": 1, "list": [1, 2]}"#;
+        let new = r#"{"meta": {"at": 2}, "metadata": 2, "list": [1, 3, 4]}"#;
+        assert_eq!(changes(old, new, &opts), "~ /metadata: 1 -> 2\n+ /list/2: 4");
+        assert!(compare(&parse("1"), &parse("2"), &Options { ignore: vec![String::new()], ..Options::default() }).is_empty());
+    }
+
+    #[test]
+    fn colors() {
+        let changes = compare(&parse(r#"{"a": 1}"#), &parse(r#"{"b": 1}"#), &Options::default());
+        assert_eq!(
+            render(&changes, true),
+            format!("{}- /a: 1\x1b[0m\n{}+ /b: 1\x1b[0m", get_code_color("r"), get_code_color("g"))
+        );
+    }
+}
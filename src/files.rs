@@ -0,0 +1,255 @@
+use crate::errors::{SResult, ToError};
+use crate::error;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Path that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// Files kept when walking a directory, patterns matching the path relative
+/// to it, or only the file name when they hold no `/`.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// Every file when empty.
+    pub include: Vec<String>,
+    /// Also prunes the directories it matches.
+    pub exclude: Vec<String>,
+}
+
+/// Glob matching: `*` and `?` within a segment, `**` across segments.
+fn glob_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob_chars(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .any(|(idx, ch)| *ch == '/' && glob_chars(rest, text.get(idx.saturating_add(1)..).unwrap_or_default()))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|idx| glob_chars(rest, text.get(idx..).unwrap_or_default())),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|idx| !text.get(..*idx).unwrap_or_default().contains(&'/'))
+            .any(|idx| glob_chars(rest, text.get(idx..).unwrap_or_default())),
+        ['?', rest @ ..] => match text.split_first() {
+            Some((ch, text)) => *ch != '/' && glob_chars(rest, text),
+            None => false,
+        },
+        [pat, rest @ ..] => match text.split_first() {
+            Some((ch, text)) => ch == pat && glob_chars(rest, text),
+            None => false,
+        },
+    }
+}
+
+pub fn glob(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    if !pattern.contains(&'/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return glob_chars(&pattern, &name.chars().collect::<Vec<_>>());
+    }
+    glob_chars(&pattern, &path.chars().collect::<Vec<_>>())
+}
+
+impl Selection {
+    fn excluded(&self, relative: &str) -> bool {
+        self.exclude.iter().any(|pattern| glob(pattern, relative))
+    }
+
+    fn included(&self, relative: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| glob(pattern, relative))
+    }
+}
+
+/// Directories are keyed by their canonical path in `visited`, so that those
+/// reached again through a symbolic link, possibly in a cycle, are skipped.
+fn walk(
+    root: &Path,
+    dir: &Path,
+    selection: &Selection,
+    visited: &mut HashSet<PathBuf>,
+    res: &mut Vec<String>,
+) -> SResult<()> {
+    let canonical = fs::canonicalize(dir).expl_error(&format!("Cannot resolve {}.", dir.display()))?;
+    if !visited.insert(canonical) {
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(dir)
+        .expl_error(&format!("Cannot read the directory {}.", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .cast_error()?;
+    entries.sort();
+    for path in entries {
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if selection.excluded(&relative) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, selection, visited, res)?;
+        } else if selection.included(&relative) {
+            res.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Replaces each directory by the selected files below it, in path order.
+/// Files given explicitly and stdin are always kept.
+pub fn expand(paths: &[String], selection: &Selection) -> SResult<Vec<String>> {
+    let mut res = vec![];
+    let mut visited = HashSet::new();
+    for path in paths {
+        let dir = Path::new(path);
+        if path != STDIN && dir.is_dir() {
+            walk(dir, dir, selection, &mut visited, &mut res)?;
+        } else {
+            res.push(path.clone());
+        }
+    }
+    Ok(res)
+}
+
+/// Reads a file, or stdin for `-`.
+pub fn load(path: &str) -> SResult<String> {
+    if path == STDIN {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content).cast_error()?;
+        return Ok(content);
+    }
+    fs::read_to_string(path).expl_error(&format!("Cannot read {path}."))
+}
+
+/// Name of a file in messages and positions.
+pub fn display(path: &str) -> &str {
+    if path == STDIN {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+/// Replaces the content of a file by writing a sibling temporary file and
+/// renaming it over the original, so that readers never see a partial file.
+/// The permissions of the original are kept.
+pub fn write_atomic(path: &str, content: &str) -> SResult<()> {
+    let target = fs::canonicalize(path).expl_error(&format!("Cannot resolve {path}."))?;
+    let permissions = fs::metadata(&target).cast_error()?.permissions();
+    let name = target
+        .file_name()
+        .ok_or_else(|| error!("{path} is not a file."))?
+        .to_string_lossy();
+    let temp = target.with_file_name(format!(".{name}.{}.tmp", process::id()));
+    let written = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::set_permissions(&temp, permissions)?;
+        fs::rename(&temp, &target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written.expl_error(&format!("Cannot write {path}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory under the system one, removed by the caller.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("parsing-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(path: &Path) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, "{}").unwrap();
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob("*.json", "a/b/c.json"));
+        assert!(!glob("*.json", "a/b/c.yaml"));
+        assert!(glob("a/*.json", "a/c.json"));
+        assert!(!glob("a/*.json", "a/b/c.json"));
+        assert!(glob("a/**/c.json", "a/c.json"));
+        assert!(glob("a/**/c.json", "a/b/d/c.json"));
+        assert!(glob("**.json", "a/b.json"));
+        assert!(glob("?.json", "b.json"));
+        assert!(!glob("?.json", "bc.json"));
+        assert!(!glob("a?b", "a/b"));
+    }
+
+    #[test]
+    fn expand_directories() {
+        let dir = scratch("expand");
+        for name in ["b.json", "a.yaml", "sub/c.json", "skip/d.json", "notes.txt"] {
+            touch(&dir.join(name));
+        }
+        let selection = Selection {
+            include: vec!["*.json".to_owned(), "*.yaml".to_owned()],
+            exclude: vec!["skip".to_owned()],
+        };
+        let root = dir.to_string_lossy().into_owned();
+        let found = expand(&[root.clone(), STDIN.to_owned(), "missing.json".to_owned()], &selection).unwrap();
+        let relative = found
+            .iter()
+            .map(|path| path.strip_prefix(&root).unwrap_or(path).to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(relative, ["/a.yaml", "/b.json", "/sub/c.json", STDIN, "missing.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles_are_walked_once() {
+        let dir = scratch("cycle");
+        touch(&dir.join("a/x.json"));
+        std::os::unix::fs::symlink("..", dir.join("a/loop")).unwrap();
+        let found = expand(&[dir.join("a").to_string_lossy().into_owned()], &Selection::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found.iter().all(|path| path.ends_with("x.json")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_writes() {
+        let dir = scratch("write");
+        let path = dir.join("f.json");
+        touch(&path);
+        let name = path.to_string_lossy().into_owned();
+        write_atomic(&name, "[1]").unwrap();
+        assert_eq!(load(&name).unwrap(), "[1]");
+        // Only the file is left, the temporary one being renamed over it.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(write_atomic(&dir.join("missing.json").to_string_lossy(), "").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_writes_keep_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("mode");
+        let path = dir.join("f.json");
+        touch(&path);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path.to_string_lossy(), "[]").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
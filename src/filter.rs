@@ -0,0 +1,1160 @@
+use crate::errors::{SResult, ToError};
+use crate::json::{ParsedValue, ParsedValueRef, ParsingItem, Scalar};
+use crate::visit::{self, Path, Visitor};
+use crate::{error, json};
+use core::convert::Infallible;
+use core::cmp::Ordering;
+use core::iter::Peekable;
+use core::ops::ControlFlow;
+use core::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Num(f64),
+    Str(String),
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Pipe,
+    Comma,
+    Colon,
+    Semicolon,
+    Question,
+    Alt,
+    Op(Op),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Reads up to the closing quote of the string opened at `column`.
+fn lex_string(chars: &mut Peekable<Chars>, column: usize) -> SResult<String> {
+    let unterminated = || error!("Unterminated string at column {column} of the filter.");
+    let mut raw = String::new();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => return Ok(json::unescape(&raw)),
+            '\\' => {
+                raw.push(ch);
+                raw.push(chars.next().ok_or_else(unterminated)?);
+            }
+            _ => raw.push(ch),
+        }
+    }
+    Err(unterminated())
+}
+
+fn lex_ident(first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut ident = String::from(first);
+    while let Some(&ch) = chars.peek() {
+        if ch.is_alphanumeric() || ch == '_' {
+            ident.push(ch);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    ident
+}
+
+/// `digits[.digits][(e|E)[+|-]digits]`, the first digit being read.
+fn lex_number(first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut num = String::from(first);
+    let digits = |num: &mut String, chars: &mut Peekable<Chars>| {
+        while let Some(next) = chars.next_if(char::is_ascii_digit) {
+            num.push(next);
+        }
+    };
+    digits(&mut num, chars);
+    if let Some(dot) = chars.next_if_eq(&'.') {
+        num.push(dot);
+        digits(&mut num, chars);
+    }
+    if let Some(exp) = chars.next_if(|next| matches!(next, 'e' | 'E')) {
+        num.push(exp);
+        if let Some(sign) = chars.next_if(|next| matches!(next, '+' | '-')) {
+            num.push(sign);
+        }
+        digits(&mut num, chars);
+    }
+    num
+}
+
+/// Tokens with the column they start at, from 1.
+fn lex(expr: &str) -> SResult<Vec<(usize, Token)>> {
+    let mut tokens = vec![];
+    let total = expr.chars().count();
+    let mut chars = expr.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let column = total.saturating_sub(chars.clone().count());
+        let token = match ch {
+            _ if ch.is_whitespace() => continue,
+            '.' => match chars.peek() {
+                Some('.') => {
+                    chars.next();
+                    Token::DotDot
+                }
+                Some('"') => {
+                    chars.next();
+                    Token::Field(lex_string(&mut chars, column)?)
+                }
+                Some(&next) if next.is_alphabetic() || next == '_' => {
+                    chars.next();
+                    Token::Field(lex_ident(next, &mut chars))
+                }
+                _ => Token::Dot,
+            },
+            '"' => Token::Str(lex_string(&mut chars, column)?),
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '|' => Token::Pipe,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '?' => Token::Question,
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '%' => Token::Op(Op::Rem),
+            '/' if chars.next_if_eq(&'/').is_some() => Token::Alt,
+            '/' => Token::Op(Op::Div),
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ne),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            _ if ch.is_ascii_digit() => {
+                let num = lex_number(ch, &mut chars);
+                Token::Num(num.parse().expl_error(&format!("Invalid number {num} at column {column} of the filter."))?)
+            }
+            _ if ch.is_alphabetic() || ch == '_' => Token::Ident(lex_ident(ch, &mut chars)),
+            _ => return Err(error!("Unexpected character '{ch}' at column {column} of the filter.")),
+        };
+        tokens.push((column, token));
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum ObjKey {
+    Name(String),
+    Computed(Filter),
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Identity,
+    Recurse,
+    Literal(ParsedValue),
+    Field(Box<Filter>, String),
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Iterate(Box<Filter>),
+    Optional(Box<Filter>),
+    Array(Option<Box<Filter>>),
+    Object(Vec<(ObjKey, Filter)>),
+    Neg(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Alt(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Binary(Op, Box<Filter>, Box<Filter>),
+    Call(String, Vec<Filter>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    /// Column of each token.
+    columns: Vec<usize>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Where the next token is, for errors.
+    fn here(&self) -> String {
+        self.columns
+            .get(self.pos)
+            .map_or_else(|| "at the end of the filter".to_owned(), |column| format!("at column {column} of the filter"))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos = self.pos.saturating_add(1);
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos = self.pos.saturating_add(1);
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &Token) -> SResult<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(error!("Expected {token:?} {}, found {:?}.", self.here(), self.peek()))
+        }
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == word)
+    }
+
+    fn pipe(&mut self) -> SResult<Filter> {
+        let left = self.comma()?;
+        if self.eat(&Token::Pipe) {
+            Ok(Filter::Pipe(Box::new(left), Box::new(self.pipe()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn comma(&mut self) -> SResult<Filter> {
+        let mut left = self.alternative()?;
+        while self.eat(&Token::Comma) {
+            left = Filter::Comma(Box::new(left), Box::new(self.alternative()?));
+        }
+        Ok(left)
+    }
+
+    fn alternative(&mut self) -> SResult<Filter> {
+        let left = self.or()?;
+        if self.eat(&Token::Alt) {
+            Ok(Filter::Alt(Box::new(left), Box::new(self.alternative()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn or(&mut self) -> SResult<Filter> {
+        let mut left = self.and()?;
+        while self.is_keyword("or") {
+            self.bump();
+            left = Filter::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> SResult<Filter> {
+        let mut left = self.comparison()?;
+        while self.is_keyword("and") {
+            self.bump();
+            left = Filter::And(Box::new(left), Box::new(self.comparison()?));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> SResult<Filter> {
+        let left = self.additive()?;
+        match self.peek() {
+            Some(&Token::Op(op @ (Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge))) => {
+                self.bump();
+                Ok(Filter::Binary(op, Box::new(left), Box::new(self.additive()?)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn additive(&mut self) -> SResult<Filter> {
+        let mut left = self.multiplicative()?;
+        while let Some(&Token::Op(op @ (Op::Add | Op::Sub))) = self.peek() {
+            self.bump();
+            left = Filter::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> SResult<Filter> {
+        let mut left = self.postfix()?;
+        while let Some(&Token::Op(op @ (Op::Mul | Op::Div | Op::Rem))) = self.peek() {
+            self.bump();
+            left = Filter::Binary(op, Box::new(left), Box::new(self.postfix()?));
+        }
+        Ok(left)
+    }
+
+    fn postfix(&mut self) -> SResult<Filter> {
+        let mut term = self.term()?;
+        loop {
+            term = match self.peek() {
+                Some(Token::Field(_)) => match self.bump() {
+                    Some(Token::Field(name)) => Filter::Field(Box::new(term), name),
+                    _ => return Err(error!("Unreachable filter state.")),
+                },
+                Some(Token::Dot) if self.tokens.get(self.pos.saturating_add(1)) == Some(&Token::LBracket) => {
+                    self.bump();
+                    continue;
+                }
+                Some(Token::LBracket) => {
+                    self.bump();
+                    self.bracket(term)?
+                }
+                Some(Token::Question) => {
+                    self.bump();
+                    Filter::Optional(Box::new(term))
+                }
+                _ => return Ok(term),
+            };
+        }
+    }
+
+    fn bracket(&mut self, term: Filter) -> SResult<Filter> {
+        if self.eat(&Token::RBracket) {
+            return Ok(Filter::Iterate(Box::new(term)));
+        }
+        let start = if self.peek() == Some(&Token::Colon) {
+            None
+        } else {
+            Some(Box::new(self.pipe()?))
+        };
+        if self.eat(&Token::Colon) {
+            let end = if self.peek() == Some(&Token::RBracket) {
+                None
+            } else {
+                Some(Box::new(self.pipe()?))
+            };
+            self.expect(&Token::RBracket)?;
+            return Ok(Filter::Slice(Box::new(term), start, end));
+        }
+        self.expect(&Token::RBracket)?;
+        Ok(Filter::Index(
+            Box::new(term),
+            start.expl_error("Empty index in filter.")?,
+        ))
+    }
+
+    fn term(&mut self) -> SResult<Filter> {
+        match self.bump() {
+            Some(Token::Dot) => Ok(Filter::Identity),
+            Some(Token::DotDot) => Ok(Filter::Recurse),
+            Some(Token::Field(name)) => Ok(Filter::Field(Box::new(Filter::Identity), name)),
+            Some(Token::Num(num)) => Ok(Filter::Literal(ParsedValue::number(num))),
+            Some(Token::Str(val)) => Ok(Filter::Literal(ParsedValue::string(&val))),
+            Some(Token::Op(Op::Sub)) => Ok(Filter::Neg(Box::new(self.postfix()?))),
+            Some(Token::LParen) => {
+                let inner = self.pipe()?;
+                self.expect(&Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::LBracket) => {
+                if self.eat(&Token::RBracket) {
+                    return Ok(Filter::Array(None));
+                }
+                let inner = self.pipe()?;
+                self.expect(&Token::RBracket)?;
+                Ok(Filter::Array(Some(Box::new(inner))))
+            }
+            Some(Token::LBrace) => self.object(),
+            Some(Token::Ident(ident)) => self.call(ident),
+            token => {
+                self.pos = self.pos.saturating_sub(1);
+                Err(error!("Unexpected token {token:?} {}.", self.here()))
+            }
+        }
+    }
+
+    fn object(&mut self) -> SResult<Filter> {
+        let mut entries = vec![];
+        if self.eat(&Token::RBrace) {
+            return Ok(Filter::Object(entries));
+        }
+        loop {
+            let key = match self.peek().cloned() {
+                Some(Token::Ident(name) | Token::Str(name)) => {
+                    self.bump();
+                    ObjKey::Name(name)
+                }
+                Some(Token::LParen) => {
+                    self.bump();
+                    let key = self.pipe()?;
+                    self.expect(&Token::RParen)?;
+                    ObjKey::Computed(key)
+                }
+                token => return Err(error!("Invalid object key {token:?} {}.", self.here())),
+            };
+            let value = if self.eat(&Token::Colon) {
+                self.object_value()?
+            } else {
+                match key {
+                    ObjKey::Name(ref name) => Filter::Field(Box::new(Filter::Identity), name.clone()),
+                    ObjKey::Computed(_) => return Err(error!("Computed object key requires a value in filter.")),
+                }
+            };
+            entries.push((key, value));
+            if self.eat(&Token::RBrace) {
+                return Ok(Filter::Object(entries));
+            }
+            self.expect(&Token::Comma)?;
+        }
+    }
+
+    /// Object values stop at commas, so they cannot use `pipe` unparenthesised.
+    fn object_value(&mut self) -> SResult<Filter> {
+        let left = self.alternative()?;
+        if self.eat(&Token::Pipe) {
+            Ok(Filter::Pipe(Box::new(left), Box::new(self.object_value()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn call(&mut self, name: String) -> SResult<Filter> {
+        match name.as_str() {
+            "true" => return Ok(Filter::Literal(ParsedValue::boolean(true))),
+            "false" => return Ok(Filter::Literal(ParsedValue::boolean(false))),
+            "null" => return Ok(Filter::Literal(ParsedValue::null())),
+            _ => (),
+        }
+        let mut args = vec![];
+        if self.eat(&Token::LParen) {
+            loop {
+                args.push(self.pipe()?);
+                if self.eat(&Token::RParen) {
+                    break;
+                }
+                self.expect(&Token::Semicolon)?;
+            }
+        }
+        Ok(Filter::Call(name, args))
+    }
+}
+
+fn compile(expr: &str) -> SResult<Filter> {
+    let (columns, tokens) = lex(expr)?.into_iter().unzip();
+    let mut parser = Parser { tokens, columns, pos: 0 };
+    let filter = parser.pipe()?;
+    match parser.peek() {
+        None => Ok(filter),
+        Some(token) => Err(error!("Unexpected trailing token {token:?} {}.", parser.here())),
+    }
+}
+
+fn truthy(value: &ParsedValue) -> bool {
+    !matches!(value.scalar(), Some(Scalar::Null | Scalar::Bool(false)))
+}
+
+const fn type_rank(value: &ParsedValue, scalar: Option<&Scalar>) -> u8 {
+    match (value, scalar) {
+        (_, Some(Scalar::Null)) => 0,
+        (_, Some(Scalar::Bool(false))) => 1,
+        (_, Some(Scalar::Bool(true))) => 2,
+        (_, Some(Scalar::Number(_))) => 3,
+        (_, Some(Scalar::Str(_))) => 4,
+        (ParsedValue::Array(_), _) => 5,
+        _ => 6,
+    }
+}
+
+fn sorted_keys(obj: &[ParsingItem]) -> Vec<String> {
+    let mut keys = obj
+        .iter()
+        .map(|item| json::unescape(&item.key))
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Total order used by jq: null < false < true < numbers < strings < arrays < objects.
+fn compare(left: &ParsedValue, right: &ParsedValue) -> Ordering {
+    let (lscalar, rscalar) = (left.scalar(), right.scalar());
+    let rank = type_rank(left, lscalar.as_ref()).cmp(&type_rank(right, rscalar.as_ref()));
+    if rank != Ordering::Equal {
+        return rank;
+    }
+    match (left, right, lscalar, rscalar) {
+        (_, _, Some(Scalar::Number(lft)), Some(Scalar::Number(rgt))) => {
+            lft.partial_cmp(&rgt).unwrap_or(Ordering::Equal)
+        }
+        (_, _, Some(Scalar::Str(lft)), Some(Scalar::Str(rgt))) => lft.cmp(&rgt),
+        (ParsedValue::Array(lft), ParsedValue::Array(rgt), _, _) => lft
+            .iter()
+            .zip(rgt)
+            .map(|(lelt, relt)| compare(lelt, relt))
+            .find(|ord| *ord != Ordering::Equal)
+            .unwrap_or_else(|| lft.len().cmp(&rgt.len())),
+        (ParsedValue::Object(lft), ParsedValue::Object(rgt), _, _) => {
+            let (lkeys, rkeys) = (sorted_keys(lft), sorted_keys(rgt));
+            lkeys.cmp(&rkeys).then_with(|| {
+                lkeys
+                    .iter()
+                    .map(|key| {
+                        let raw = json::escape(key);
+                        match (json::get(lft, &raw), json::get(rgt, &raw)) {
+                            (Some(lval), Some(rval)) => compare(lval, rval),
+                            _ => Ordering::Equal,
+                        }
+                    })
+                    .find(|ord| *ord != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => Ordering::Equal,
+    }
+}
+
+fn number(value: &ParsedValue, context: &str) -> SResult<f64> {
+    match value.scalar() {
+        Some(Scalar::Number(num)) => Ok(num),
+        _ => Err(error!("{context}: {} is not a number.", value.type_name())),
+    }
+}
+
+fn set(obj: &mut Vec<ParsingItem>, key: String, value: ParsedValue) {
+    obj.retain(|item| item.key != key);
+    obj.push(ParsingItem { key, value });
+}
+
+fn deep_merge(left: &ParsedValue, right: &ParsedValue) -> ParsedValue {
+    match (left, right) {
+        (ParsedValue::Object(lft), ParsedValue::Object(rgt)) => {
+            let mut res = lft.clone();
+            for item in rgt {
+                let merged = match json::get(&res, &item.key) {
+                    Some(prev) => deep_merge(prev, &item.value),
+                    None => item.value.clone(),
+                };
+                set(&mut res, item.key.clone(), merged);
+            }
+            ParsedValue::Object(res)
+        }
+        _ => right.clone(),
+    }
+}
+
+#[allow(clippy::float_arithmetic)]
+fn arithmetic(op: Op, left: &ParsedValue, right: &ParsedValue) -> SResult<ParsedValue> {
+    let (lscalar, rscalar) = (left.scalar(), right.scalar());
+    Ok(match (op, left, right, lscalar, rscalar) {
+        (Op::Add, _, _, Some(Scalar::Null), _) => right.clone(),
+        (Op::Add, _, _, _, Some(Scalar::Null)) => left.clone(),
+        (_, _, _, Some(Scalar::Number(lft)), Some(Scalar::Number(rgt))) => ParsedValue::number(match op {
+            Op::Add => lft + rgt,
+            Op::Sub => lft - rgt,
+            Op::Mul => lft * rgt,
+            Op::Div if rgt == 0.0_f64 => return Err(error!("Division of {lft} by zero.")),
+            Op::Div => lft / rgt,
+            Op::Rem if rgt.trunc() == 0.0_f64 => return Err(error!("Remainder of {lft} by zero.")),
+            Op::Rem => lft.trunc() % rgt.trunc(),
+            _ => return Err(error!("Unreachable operator {op:?}.")),
+        }),
+        (Op::Add, _, _, Some(Scalar::Str(lft)), Some(Scalar::Str(rgt))) => ParsedValue::string(&(lft + &rgt)),
+        (Op::Add, ParsedValue::Array(lft), ParsedValue::Array(rgt), _, _) => {
+            ParsedValue::Array(lft.iter().chain(rgt).cloned().collect())
+        }
+        (Op::Add, ParsedValue::Object(lft), ParsedValue::Object(rgt), _, _) => {
+            let mut res = lft.clone();
+            for item in rgt {
+                set(&mut res, item.key.clone(), item.value.clone());
+            }
+            ParsedValue::Object(res)
+        }
+        (Op::Sub, ParsedValue::Array(lft), ParsedValue::Array(rgt), _, _) => ParsedValue::Array(
+            lft.iter()
+                .filter(|elt| !rgt.iter().any(|other| other.same(elt)))
+                .cloned()
+                .collect(),
+        ),
+        (Op::Mul, ParsedValue::Object(_), ParsedValue::Object(_), _, _) => deep_merge(left, right),
+        (Op::Div, _, _, Some(Scalar::Str(lft)), Some(Scalar::Str(rgt))) => ParsedValue::Array(
+            lft.split(rgt.as_str()).map(ParsedValue::string).collect(),
+        ),
+        _ => {
+            return Err(error!(
+                "{} and {} cannot be combined with {op:?}.",
+                left.type_name(),
+                right.type_name()
+            ))
+        }
+    })
+}
+
+fn binary(op: Op, left: &ParsedValue, right: &ParsedValue) -> SResult<ParsedValue> {
+    let ord = compare(left, right);
+    Ok(ParsedValue::boolean(match op {
+        Op::Eq => left.same(right),
+        Op::Ne => !left.same(right),
+        Op::Lt => ord == Ordering::Less,
+        Op::Le => ord != Ordering::Greater,
+        Op::Gt => ord == Ordering::Greater,
+        Op::Ge => ord != Ordering::Less,
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem => return arithmetic(op, left, right),
+    }))
+}
+
+fn index(value: &ParsedValue, idx: &ParsedValue) -> SResult<ParsedValue> {
+    match (value, value.scalar(), idx.scalar()) {
+        (_, Some(Scalar::Null), _) => Ok(ParsedValue::null()),
+        (ParsedValue::Object(obj), _, Some(Scalar::Str(key))) => Ok(json::get(obj, &json::escape(&key))
+            .cloned()
+            .unwrap_or_else(ParsedValue::null)),
+        (ParsedValue::Array(arr), _, Some(Scalar::Number(num))) => {
+            #[allow(clippy::cast_possible_truncation, clippy::as_conversions)]
+            let pos = num.floor() as i64;
+            let pos = if pos < 0 {
+                i64::try_from(arr.len()).unwrap_or(i64::MAX).saturating_add(pos)
+            } else {
+                pos
+            };
+            Ok(usize::try_from(pos)
+                .ok()
+                .and_then(|pos| arr.get(pos))
+                .cloned()
+                .unwrap_or_else(ParsedValue::null))
+        }
+        _ => Err(error!(
+            "Cannot index {} with {}.",
+            value.type_name(),
+            idx.type_name()
+        )),
+    }
+}
+
+fn slice_bound(bound: Option<&ParsedValue>, len: usize, default: usize) -> SResult<usize> {
+    let Some(bound) = bound else {
+        return Ok(default);
+    };
+    if matches!(bound.scalar(), Some(Scalar::Null)) {
+        return Ok(default);
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::as_conversions)]
+    let pos = number(bound, "Slice bound")?.floor() as i64;
+    let ilen = i64::try_from(len).unwrap_or(i64::MAX);
+    let pos = if pos < 0 { ilen.saturating_add(pos) } else { pos };
+    Ok(usize::try_from(pos.clamp(0, ilen)).unwrap_or_default())
+}
+
+fn slice(value: &ParsedValue, start: Option<&ParsedValue>, end: Option<&ParsedValue>) -> SResult<ParsedValue> {
+    match (value, value.scalar()) {
+        (_, Some(Scalar::Null)) => Ok(ParsedValue::null()),
+        (ParsedValue::Array(arr), _) => {
+            let beg = slice_bound(start, arr.len(), 0)?;
+            let fin = slice_bound(end, arr.len(), arr.len())?.max(beg);
+            Ok(ParsedValue::Array(arr.get(beg..fin).unwrap_or_default().to_vec()))
+        }
+        (_, Some(Scalar::Str(val))) => {
+            let chars = val.chars().collect::<Vec<_>>();
+            let beg = slice_bound(start, chars.len(), 0)?;
+            let fin = slice_bound(end, chars.len(), chars.len())?.max(beg);
+            Ok(ParsedValue::string(
+                &chars.get(beg..fin).unwrap_or_default().iter().collect::<String>(),
+            ))
+        }
+        _ => Err(error!("Cannot slice {}.", value.type_name())),
+    }
+}
+
+fn children(value: &ParsedValue) -> SResult<Vec<ParsedValue>> {
+    match json::to_ref(value) {
+        ParsedValueRef::Array(arr) => Ok(arr.clone()),
+        ParsedValueRef::Object(obj) => Ok(obj.iter().map(|item| item.value.clone()).collect()),
+        ParsedValueRef::Value(_) => Err(error!("Cannot iterate over {}.", value.type_name())),
+    }
+}
+
+/// Collects every value of the tree, parents first, for `..`.
+struct Recurse(Vec<ParsedValue>);
+
+impl Visitor for Recurse {
+    type Break = Infallible;
+
+    fn enter(&mut self, _: &Path, value: &ParsedValue) -> ControlFlow<Infallible> {
+        self.0.push(value.clone());
+        ControlFlow::Continue(())
+    }
+}
+
+fn entries(value: &ParsedValue) -> SResult<Vec<ParsedValue>> {
+    match value {
+        ParsedValue::Object(obj) => Ok(obj
+            .iter()
+            .map(|item| {
+                json!({"key": ParsedValue::Value(format!("\"{}\"", item.key)), "value": item.value.clone()})
+            })
+            .collect()),
+        _ => Err(error!("{} has no keys.", value.type_name())),
+    }
+}
+
+fn from_entries(value: &ParsedValue) -> SResult<ParsedValue> {
+    let mut res = vec![];
+    for entry in children(value)? {
+        let ParsedValue::Object(obj) = entry else {
+            return Err(error!("from_entries expects objects, found {}.", entry.type_name()));
+        };
+        let find = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| json::get(&obj, name).filter(|found| truthy(found)))
+                .cloned()
+        };
+        let key = match find(&["key", "k", "name", "Name", "Key", "K"]).and_then(|key| key.scalar()) {
+            Some(Scalar::Str(key)) => key,
+            Some(Scalar::Number(num)) => json::format_number(num),
+            Some(Scalar::Bool(val)) => val.to_string(),
+            _ => return Err(error!("from_entries found an entry without a string key.")),
+        };
+        let val = find(&["value", "v", "Value", "V"]).unwrap_or_else(ParsedValue::null);
+        set(&mut res, json::escape(&key), val);
+    }
+    Ok(ParsedValue::Object(res))
+}
+
+fn length(value: &ParsedValue) -> SResult<ParsedValue> {
+    #[allow(clippy::cast_precision_loss, clippy::as_conversions)]
+    let len = match (value, value.scalar()) {
+        (_, Some(Scalar::Null)) => 0.0_f64,
+        (_, Some(Scalar::Number(num))) => num.abs(),
+        (_, Some(Scalar::Str(val))) => val.chars().count() as f64,
+        (ParsedValue::Array(arr), _) => arr.len() as f64,
+        (ParsedValue::Object(obj), _) => obj.len() as f64,
+        _ => return Err(error!("{} has no length.", value.type_name())),
+    };
+    Ok(ParsedValue::number(len))
+}
+
+fn keys(value: &ParsedValue, sort: bool) -> SResult<ParsedValue> {
+    #[allow(clippy::cast_precision_loss, clippy::as_conversions)]
+    match value {
+        ParsedValue::Object(obj) if sort => Ok(ParsedValue::Array(
+            sorted_keys(obj).iter().map(|key| ParsedValue::string(key)).collect(),
+        )),
+        ParsedValue::Object(obj) => Ok(ParsedValue::Array(
+            obj.iter()
+                .map(|item| ParsedValue::Value(format!("\"{}\"", item.key)))
+                .collect(),
+        )),
+        ParsedValue::Array(arr) => Ok(ParsedValue::Array(
+            (0..arr.len()).map(|idx| ParsedValue::number(idx as f64)).collect(),
+        )),
+        ParsedValue::Value(_) => Err(error!("{} has no keys.", value.type_name())),
+    }
+}
+
+fn tostring(value: &ParsedValue) -> SResult<ParsedValue> {
+    match value.scalar() {
+        Some(Scalar::Str(_)) => Ok(value.clone()),
+        _ => Ok(ParsedValue::string(&json::stringify(value)?)),
+    }
+}
+
+fn tonumber(value: &ParsedValue) -> SResult<ParsedValue> {
+    match value.scalar() {
+        Some(Scalar::Number(_)) => Ok(value.clone()),
+        Some(Scalar::Str(val)) => val
+            .parse::<f64>()
+            .map(ParsedValue::number)
+            .map_err(|_| error!("Cannot parse {val:?} as a number.")),
+        _ => Err(error!("{} cannot be parsed as a number.", value.type_name())),
+    }
+}
+
+impl Filter {
+    /// Runs `self` then `then` on every output.
+    fn then(&self, input: &ParsedValue, then: &Self) -> SResult<Vec<ParsedValue>> {
+        let mut res = vec![];
+        for value in self.eval(input)? {
+            res.extend(then.eval(&value)?);
+        }
+        Ok(res)
+    }
+
+    fn pairs(&self, other: &Self, input: &ParsedValue) -> SResult<Vec<(ParsedValue, ParsedValue)>> {
+        let lefts = self.eval(input)?;
+        let mut res = vec![];
+        for right in other.eval(input)? {
+            for left in &lefts {
+                res.push((left.clone(), right.clone()));
+            }
+        }
+        Ok(res)
+    }
+
+    fn eval(&self, input: &ParsedValue) -> SResult<Vec<ParsedValue>> {
+        match self {
+            Self::Identity => Ok(vec![input.clone()]),
+            Self::Recurse => {
+                let mut recurse = Recurse(vec![]);
+                let _ = visit::visit(&mut recurse, input);
+                Ok(recurse.0)
+            }
+            Self::Literal(value) => Ok(vec![value.clone()]),
+            Self::Field(target, name) => target
+                .eval(input)?
+                .iter()
+                .map(|value| index(value, &ParsedValue::string(name)))
+                .collect(),
+            Self::Index(target, idx) => {
+                let mut res = vec![];
+                for value in target.eval(input)? {
+                    for key in idx.eval(input)? {
+                        res.push(index(&value, &key)?);
+                    }
+                }
+                Ok(res)
+            }
+            Self::Slice(target, start, end) => {
+                let starts = start.as_ref().map_or_else(|| Ok(vec![ParsedValue::null()]), |start| start.eval(input))?;
+                let ends = end.as_ref().map_or_else(|| Ok(vec![ParsedValue::null()]), |end| end.eval(input))?;
+                let mut res = vec![];
+                for value in target.eval(input)? {
+                    for beg in &starts {
+                        for fin in &ends {
+                            res.push(slice(&value, Some(beg), Some(fin))?);
+                        }
+                    }
+                }
+                Ok(res)
+            }
+            Self::Iterate(target) => {
+                let mut res = vec![];
+                for value in target.eval(input)? {
+                    res.extend(children(&value)?);
+                }
+                Ok(res)
+            }
+            Self::Optional(inner) => Ok(inner.eval(input).unwrap_or_default()),
+            Self::Array(None) => Ok(vec![ParsedValue::Array(vec![])]),
+            Self::Array(Some(inner)) => Ok(vec![ParsedValue::Array(inner.eval(input)?)]),
+            Self::Object(entries) => {
+                let mut objects = vec![vec![]];
+                for (key, value) in entries {
+                    let keys = match key {
+                        ObjKey::Name(name) => vec![json::escape(name)],
+                        ObjKey::Computed(filter) => filter
+                            .eval(input)?
+                            .into_iter()
+                            .map(|key| match key.scalar() {
+                                Some(Scalar::Str(key)) => Ok(json::escape(&key)),
+                                _ => Err(error!("Object keys must be strings, found {}.", key.type_name())),
+                            })
+                            .collect::<SResult<Vec<_>>>()?,
+                    };
+                    let values = value.eval(input)?;
+                    let mut next = vec![];
+                    for obj in &objects {
+                        for key in &keys {
+                            for val in &values {
+                                let mut obj = obj.clone();
+                                set(&mut obj, key.clone(), val.clone());
+                                next.push(obj);
+                            }
+                        }
+                    }
+                    objects = next;
+                }
+                Ok(objects.into_iter().map(ParsedValue::Object).collect())
+            }
+            Self::Neg(inner) => inner
+                .eval(input)?
+                .iter()
+                .map(|value| {
+                    arithmetic(Op::Sub, &ParsedValue::number(0.0_f64), value)
+                })
+                .collect(),
+            Self::Pipe(left, right) => left.then(input, right),
+            Self::Comma(left, right) => {
+                let mut res = left.eval(input)?;
+                res.extend(right.eval(input)?);
+                Ok(res)
+            }
+            Self::Alt(left, right) => {
+                let found = left
+                    .eval(input)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(truthy)
+                    .collect::<Vec<_>>();
+                if found.is_empty() {
+                    right.eval(input)
+                } else {
+                    Ok(found)
+                }
+            }
+            Self::And(left, right) | Self::Or(left, right) => {
+                let is_and = matches!(self, Self::And(..));
+                let mut res = vec![];
+                for value in left.eval(input)? {
+                    if truthy(&value) != is_and {
+                        res.push(ParsedValue::boolean(!is_and));
+                        continue;
+                    }
+                    for other in right.eval(input)? {
+                        res.push(ParsedValue::boolean(truthy(&other)));
+                    }
+                }
+                Ok(res)
+            }
+            Self::Binary(op, left, right) => left
+                .pairs(right, input)?
+                .iter()
+                .map(|(lft, rgt)| binary(*op, lft, rgt))
+                .collect(),
+            Self::Call(name, args) => call(name, args, input),
+        }
+    }
+}
+
+fn call(name: &str, args: &[Filter], input: &ParsedValue) -> SResult<Vec<ParsedValue>> {
+    let one = |value: SResult<ParsedValue>| value.map(|val| vec![val]);
+    match (name, args) {
+        ("empty", []) => Ok(vec![]),
+        ("not", []) => one(Ok(ParsedValue::boolean(!truthy(input)))),
+        ("length", []) => one(length(input)),
+        ("keys", []) => one(keys(input, true)),
+        ("keys_unsorted", []) => one(keys(input, false)),
+        ("type", []) => one(Ok(ParsedValue::string(input.type_name()))),
+        ("to_entries", []) => one(entries(input).map(ParsedValue::Array)),
+        ("from_entries", []) => one(from_entries(input)),
+        ("tostring", []) => one(tostring(input)),
+        ("tonumber", []) => one(tonumber(input)),
+        ("values", []) => Ok(if matches!(input.scalar(), Some(Scalar::Null)) {
+            vec![]
+        } else {
+            vec![input.clone()]
+        }),
+        ("add", []) => {
+            let mut acc = ParsedValue::null();
+            for value in children(input)? {
+                acc = arithmetic(Op::Add, &acc, &value)?;
+            }
+            one(Ok(acc))
+        }
+        ("sort", []) => {
+            let mut arr = children(input)?;
+            arr.sort_by(compare);
+            one(Ok(ParsedValue::Array(arr)))
+        }
+        ("reverse", []) => {
+            let mut arr = children(input)?;
+            arr.reverse();
+            one(Ok(ParsedValue::Array(arr)))
+        }
+        ("select", [cond]) => Ok(cond
+            .eval(input)?
+            .iter()
+            .filter(|value| truthy(value))
+            .map(|_| input.clone())
+            .collect()),
+        ("map", [func]) => {
+            let mut res = vec![];
+            for value in children(input)? {
+                res.extend(func.eval(&value)?);
+            }
+            one(Ok(ParsedValue::Array(res)))
+        }
+        ("with_entries", [func]) => {
+            let mut mapped = vec![];
+            for entry in entries(input)? {
+                mapped.extend(func.eval(&entry)?);
+            }
+            one(from_entries(&ParsedValue::Array(mapped)))
+        }
+        ("has", [key]) => key
+            .eval(input)?
+            .iter()
+            .map(|key| match (input, key.scalar()) {
+                (ParsedValue::Object(obj), Some(Scalar::Str(key))) => {
+                    Ok(ParsedValue::boolean(json::get(obj, &json::escape(&key)).is_some()))
+                }
+                (ParsedValue::Array(arr), Some(Scalar::Number(num))) => {
+                    #[allow(clippy::cast_precision_loss, clippy::as_conversions)]
+                    Ok(ParsedValue::boolean(num >= 0.0_f64 && num < arr.len() as f64))
+                }
+                _ => Err(error!("Cannot check whether {} has a {} key.", input.type_name(), key.type_name())),
+            })
+            .collect(),
+        _ => Err(error!("Unknown filter function {name}/{}.", args.len())),
+    }
+}
+
+/// Every output of the filter `expr` over `root`, one per line.
+pub fn run(root: &ParsedValue, expr: &str) -> SResult<String> {
+    let filter = compile(expr)?;
+    Ok(filter
+        .eval(root)?
+        .iter()
+        .map(json::stringify)
+        .collect::<SResult<Vec<_>>>()?
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    const DOC: &str = r#"{
+        "name": "box",
+        "size": {"w": 2, "h": 3.5},
+        "tags": ["a", "b", "c"],
+        "items": [{"id": 1, "ok": true}, {"id": 2, "ok": false}, {"id": 3}]
+    }"#;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    /// Outputs of `expr` over `doc`, compared through their serialization.
+    fn outputs(doc: &str, expr: &str) -> Vec<String> {
+        compile(expr)
+            .unwrap()
+            .eval(&parse(doc))
+            .unwrap()
+            .iter()
+            .map(|value| json::stringify(value).unwrap())
+            .collect()
+    }
+
+    fn check(expr: &str, expected: &[&str]) {
+        let expected = expected
+            .iter()
+            .map(|text| json::stringify(&parse(text)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(outputs(DOC, expr), expected, "{expr}");
+    }
+
+    fn failure(expr: &str) -> String {
+        compile(expr)
+            .and_then(|filter| filter.eval(&parse(DOC)))
+            .unwrap_err()
+    }
+
+    #[test]
+    fn paths() {
+        check(".", &[DOC]);
+        check(".name", &[r#""box""#]);
+        check(".size.w", &["2"]);
+        check(r#".["size"]["h"]"#, &["3.5"]);
+        check(r#"."name""#, &[r#""box""#]);
+        check(".tags[1]", &[r#""b""#]);
+        check(".tags[-1]", &[r#""c""#]);
+        check(".tags[1:]", &[r#"["b", "c"]"#]);
+        check(".missing", &["null"]);
+        check(".items[5]", &["null"]);
+        check(".name.x?", &[]);
+    }
+
+    #[test]
+    fn iteration_and_pipes() {
+        check(".tags[]", &[r#""a""#, r#""b""#, r#""c""#]);
+        check(".size[]", &["2", "3.5"]);
+        check(".items[] | .id", &["1", "2", "3"]);
+        check(".items[].id", &["1", "2", "3"]);
+        check(".name, .size.w", &[r#""box""#, "2"]);
+        check(r#"[.. | select(type == "number")] | length"#, &["5"]);
+    }
+
+    #[test]
+    fn select_and_map() {
+        check(".items[] | select(.ok) | .id", &["1"]);
+        check(".items | map(.id * 10)", &["[10, 20, 30]"]);
+        check(".items | map(select(.id >= 2) | .id)", &["[2, 3]"]);
+        check("[.items[] | .ok // \"none\"]", &[r#"[true, "none", "none"]"#]);
+    }
+
+    #[test]
+    fn construction() {
+        check("[.tags[0], .size.w]", &[r#"["a", 2]"#]);
+        check("[]", &["[]"]);
+        check("{name, w: .size.w}", &[r#"{"name": "box", "w": 2}"#]);
+        check("{(.name): 1}", &[r#"{"box": 1}"#]);
+        check(r#"{"a b": .tags | length}"#, &[r#"{"a b": 3}"#]);
+        check("{a: (1, 2)}", &[r#"{"a": 1}"#, r#"{"a": 2}"#]);
+    }
+
+    #[test]
+    fn arithmetic() {
+        check(".size.w + .size.h", &["5.5"]);
+        check("1 - 2 * 3", &["-5"]);
+        check("(1 - 2) * 3", &["-3"]);
+        check("7 % 3, 7 / 2", &["1", "3.5"]);
+        check("1e-5 * 1e5, 2E+2, 1.5e1", &["1", "200", "15"]);
+        check("-.size.w", &["-2"]);
+        check(".name + \"!\"", &[r#""box!""#]);
+        check(".tags + [\"d\"]", &[r#"["a", "b", "c", "d"]"#]);
+        check("{a: 1} + {b: 2}", &[r#"{"a": 1, "b": 2}"#]);
+        check("null + 1", &["1"]);
+        assert!(failure(".name - 1").contains("string"));
+        assert!(failure("1 / 0").contains("zero"));
+    }
+
+    #[test]
+    fn comparisons() {
+        check("1 < 2, 2 <= 1, 1 == 1.0, \"a\" != \"a\"", &["true", "false", "true", "false"]);
+        check("null < false, [] > \"z\", {} > []", &["true", "true", "true"]);
+        check(".size.w > 1 and .name == \"box\"", &["true"]);
+        check("false or null, (null | not)", &["false", "true"]);
+    }
+
+    #[test]
+    fn builtins() {
+        check(".size | keys", &[r#"["h", "w"]"#]);
+        check(".size | keys_unsorted", &[r#"["w", "h"]"#]);
+        check(".tags, .name, .size, null | length", &["3", "3", "2", "0"]);
+        check(".size | to_entries", &[r#"[{"key": "w", "value": 2}, {"key": "h", "value": 3.5}]"#]);
+        check(".size | to_entries | from_entries", &[r#"{"w": 2, "h": 3.5}"#]);
+        check(r#"[{"name": "a", "value": 1}, {"k": "b", "v": 2}] | from_entries"#, &[r#"{"a": 1, "b": 2}"#]);
+        check(".tags | has(2), has(3)", &["true", "false"]);
+        check(".tags | reverse | sort", &[r#"["a", "b", "c"]"#]);
+        check("[.items[].id] | add", &["6"]);
+        check("(.name | type), (.size.w | tostring), (\"4\" | tonumber)", &[r#""string""#, r#""2""#, "4"]);
+    }
+
+    #[test]
+    fn alternative() {
+        check(".missing // .name", &[r#""box""#]);
+        check(".items[0].ok // 1", &["true"]);
+        check("(false, null) // 1", &["1"]);
+        check("empty // 2", &["2"]);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert!(failure(".a | @").contains("'@' at column 6"));
+        assert!(failure(".tags[1").contains("at the end of the filter"));
+        assert!(failure("[1, 2) ").contains("at column 6"));
+        assert!(failure("{1: 2}").contains("at column 2"));
+        assert!(failure(". )").contains("at column 3"));
+        assert!(failure(r#".a + "x"#).contains("Unterminated string at column 6"));
+        assert!(failure("1e").contains("Invalid number 1e at column 1"));
+        assert!(failure("nope(1)").contains("nope/1"));
+    }
+
+    #[test]
+    fn run_prints_one_output_per_line() {
+        assert_eq!(run(&parse(DOC), ".tags[0], .size.w").unwrap(), "\"a\"\n2");
+    }
+}
@@ -0,0 +1,351 @@
+use crate::errors::SResult;
+use crate::json::{ParsedValue, ParsingItem};
+use crate::visit::{self, Path, VisitorMut};
+use crate::{error, json, pointer};
+use core::ops::ControlFlow;
+
+/// How array indexes appear in path keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arrays {
+    /// `a.b[0].c`
+    #[default]
+    Brackets,
+    /// `a.b.0.c`
+    Separator,
+}
+
+impl Arrays {
+    pub fn from_name(name: &str) -> SResult<Self> {
+        match name {
+            "brackets" | "bracket" => Ok(Self::Brackets),
+            "separator" | "dots" | "index" => Ok(Self::Separator),
+            _ => Err(error!("Unknown array notation {name:?}, expected brackets or separator.")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Keys {
+    Path { separator: String, arrays: Arrays },
+    /// JSON Pointers, where the separator cannot clash with the keys.
+    Pointer,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self::Path {
+            separator: ".".to_owned(),
+            arrays: Arrays::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Key(String),
+    Index(usize),
+}
+
+/// `[0][1]` at the start of `text` when nothing but a separator follows it,
+/// with what follows.
+fn trailing_indexes<'key>(text: &'key str, separator: &str) -> Option<(Vec<usize>, &'key str)> {
+    let mut indexes = vec![];
+    let mut rest = text;
+    while let Some(inner) = rest.strip_prefix('[') {
+        let (digits, after) = inner.split_once(']')?;
+        indexes.push(digits.parse::<usize>().ok()?);
+        rest = after;
+    }
+    (rest.is_empty() || (!separator.is_empty() && rest.starts_with(separator))).then_some((indexes, rest))
+}
+
+/// Splits a path key on the separators that are not escaped, each segment
+/// with its trailing bracketed indexes.
+fn segments(key: &str, separator: &str, brackets: bool) -> Vec<(String, Vec<usize>)> {
+    let mut res = vec![];
+    let mut name = String::new();
+    let mut indexes = vec![];
+    let mut rest = key;
+    while let Some(ch) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix('\\') {
+            let len = if !separator.is_empty() && after.starts_with(separator) {
+                separator.len()
+            } else {
+                after.chars().next().map_or(0, char::len_utf8)
+            };
+            match after.get(..len).filter(|escaped| !escaped.is_empty()) {
+                Some(escaped) => name.push_str(escaped),
+                None => name.push('\\'),
+            }
+            rest = after.get(len..).unwrap_or_default();
+        } else if let Some(after) = rest.strip_prefix(separator).filter(|_| !separator.is_empty()) {
+            res.push((name.clone(), indexes.clone()));
+            name.clear();
+            indexes.clear();
+            rest = after;
+        } else if let Some((found, after)) = trailing_indexes(rest, separator).filter(|_| brackets && ch == '[') {
+            indexes = found;
+            rest = after;
+        } else {
+            name.push(ch);
+            rest = rest.get(ch.len_utf8()..).unwrap_or_default();
+        }
+    }
+    res.push((name, indexes));
+    res
+}
+
+impl Keys {
+    /// Escapes the backslash, the separator and, when they note indexes, the
+    /// brackets, so that a key always reads back as one segment.
+    fn escape(&self, key: &str) -> String {
+        let Self::Path { separator, arrays } = self else {
+            return key.to_owned();
+        };
+        let mut res = String::new();
+        let mut rest = key;
+        while let Some(ch) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix(separator.as_str()).filter(|_| !separator.is_empty()) {
+                res.push('\\');
+                res.push_str(separator);
+                rest = after;
+                continue;
+            }
+            if ch == '\\' || (*arrays == Arrays::Brackets && matches!(ch, '[' | ']')) {
+                res.push('\\');
+            }
+            res.push(ch);
+            rest = rest.get(ch.len_utf8()..).unwrap_or_default();
+        }
+        res
+    }
+
+    /// `path` is `None` at the root, which an empty key is not.
+    fn push_key(&self, path: Option<&str>, key: &str) -> String {
+        match (self, path) {
+            (Self::Pointer, _) => pointer::push(path.unwrap_or_default(), key),
+            (Self::Path { .. }, None) => self.escape(key),
+            (Self::Path { separator, .. }, Some(path)) => format!("{path}{separator}{}", self.escape(key)),
+        }
+    }
+
+    fn push_index(&self, path: Option<&str>, idx: usize) -> String {
+        match self {
+            Self::Path {
+                arrays: Arrays::Brackets,
+                ..
+            } => format!("{}[{idx}]", path.unwrap_or_default()),
+            Self::Path { .. } | Self::Pointer => self.push_key(path, &idx.to_string()),
+        }
+    }
+
+    /// Splits a key back into tokens, undoing `escape`, all-digit segments
+    /// being indexes unless arrays use brackets.
+    fn tokens(&self, key: &str) -> SResult<Vec<Token>> {
+        let segment = |part: String| match part.parse::<usize>() {
+            Ok(idx) if !(part.len() > 1 && part.starts_with('0')) => Token::Index(idx),
+            _ => Token::Key(part),
+        };
+        let (separator, arrays) = match self {
+            Self::Pointer => return Ok(pointer::parse(key)?.into_iter().map(segment).collect()),
+            Self::Path { separator, arrays } => (separator, arrays),
+        };
+        if key.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut res = vec![];
+        let parts = segments(key, separator, *arrays == Arrays::Brackets);
+        for (nb, (name, indexes)) in parts.into_iter().enumerate() {
+            if *arrays == Arrays::Separator {
+                res.push(segment(name));
+                continue;
+            }
+            if !(name.is_empty() && nb == 0 && !indexes.is_empty()) {
+                res.push(Token::Key(name));
+            }
+            res.extend(indexes.into_iter().map(Token::Index));
+        }
+        Ok(res)
+    }
+}
+
+fn walk(value: &ParsedValue, path: Option<&str>, keys: &Keys, res: &mut Vec<ParsingItem>) {
+    match value {
+        ParsedValue::Object(obj) if !obj.is_empty() => {
+            for item in obj {
+                walk(&item.value, Some(&keys.push_key(path, &json::unescape(&item.key))), keys, res);
+            }
+        }
+        ParsedValue::Array(arr) if !arr.is_empty() => {
+            for (idx, elt) in arr.iter().enumerate() {
+                walk(elt, Some(&keys.push_index(path, idx)), keys, res);
+            }
+        }
+        _ => res.push(ParsingItem {
+            key: json::escape(path.unwrap_or_default()),
+            value: value.clone(),
+        }),
+    }
+}
+
+/// Single-level object mapping the path of every leaf to its value, empty
+/// arrays and objects being kept as leaves so that `unflatten` restores them.
+pub fn flatten(value: &ParsedValue, keys: &Keys) -> ParsedValue {
+    let mut res = vec![];
+    walk(value, None, keys, &mut res);
+    ParsedValue::Object(res)
+}
+
+/// Not yet assigned, as the empty raw token is never a valid scalar.
+fn unset() -> ParsedValue {
+    ParsedValue::Value(String::new())
+}
+
+fn is_unset(value: &ParsedValue) -> bool {
+    matches!(value, ParsedValue::Value(raw) if raw.is_empty())
+}
+
+fn insert(node: &mut ParsedValue, tokens: &[Token], value: ParsedValue) -> SResult<()> {
+    let Some((first, rest)) = tokens.split_first() else {
+        *node = value;
+        return Ok(());
+    };
+    match first {
+        Token::Key(key) => {
+            if is_unset(node) {
+                *node = ParsedValue::Object(vec![]);
+            }
+            let ParsedValue::Object(obj) = node else {
+                return Err(format!("{key:?} is a key of a value that is not an object"));
+            };
+            let idx = match pointer::find_key(obj, key) {
+                Some(idx) => idx,
+                None => {
+                    obj.push(ParsingItem {
+                        key: json::escape(key),
+                        value: unset(),
+                    });
+                    obj.len().saturating_sub(1)
+                }
+            };
+            match obj.get_mut(idx) {
+                Some(item) => insert(&mut item.value, rest, value),
+                None => Ok(()),
+            }
+        }
+        // A digit segment also names the key of an object created by an
+        // earlier path.
+        Token::Index(idx) if matches!(node, ParsedValue::Object(_)) => {
+            let mut tokens = tokens.to_vec();
+            if let Some(first) = tokens.first_mut() {
+                *first = Token::Key(idx.to_string());
+            }
+            insert(node, &tokens, value)
+        }
+        Token::Index(idx) => {
+            if is_unset(node) {
+                *node = ParsedValue::Array(vec![]);
+            }
+            let ParsedValue::Array(arr) = node else {
+                return Err(format!("{idx} is an index of a value that is not an array"));
+            };
+            if arr.len() <= *idx {
+                arr.resize(idx.saturating_add(1), unset());
+            }
+            match arr.get_mut(*idx) {
+                Some(elt) => insert(elt, rest, value),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// Fills the holes of sparse arrays with null.
+struct FillHoles;
+
+impl VisitorMut for FillHoles {
+    type Break = ();
+
+    fn enter_mut(&mut self, _: &Path, value: &mut ParsedValue) -> ControlFlow<()> {
+        if is_unset(value) {
+            *value = ParsedValue::null();
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Rebuilds the nested document from an object of path keys.
+pub fn unflatten(value: &ParsedValue, keys: &Keys) -> SResult<ParsedValue> {
+    let ParsedValue::Object(obj) = value else {
+        return Err(error!("Only an object of path keys can be unflattened."));
+    };
+    let mut res = ParsedValue::Object(vec![]);
+    if !obj.is_empty() {
+        res = unset();
+    }
+    for item in obj {
+        let key = json::unescape(&item.key);
+        insert(&mut res, &keys.tokens(&key)?, item.value.clone())
+            .map_err(|err| error!("Cannot unflatten {key:?}: {err}."))?;
+    }
+    let _ = visit::visit_mut(&mut FillHoles, &mut res);
+    Ok(res)
+}
+
+pub fn run(value: &ParsedValue, keys: &Keys, reverse: bool) -> SResult<ParsedValue> {
+    if reverse {
+        unflatten(value, keys)
+    } else {
+        Ok(flatten(value, keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn round_trip(text: &str, keys: &Keys, flat: &str) {
+        let flattened = flatten(&parse(text), keys);
+        assert_eq!(json::stringify(&flattened).unwrap(), json::stringify(&parse(flat)).unwrap());
+        let restored = unflatten(&flattened, keys).unwrap();
+        assert_eq!(json::stringify(&restored).unwrap(), json::stringify(&parse(text)).unwrap());
+    }
+
+    #[test]
+    fn separator_in_keys() {
+        round_trip(r#"{"a.b": 1, "a": {"b": 2}}"#, &Keys::default(), r#"{"a\\.b": 1, "a.b": 2}"#);
+        let keys = Keys::Path {
+            separator: "::".to_owned(),
+            arrays: Arrays::Separator,
+        };
+        round_trip(r#"{"a::b": {"c": [1]}}"#, &keys, r#"{"a\\::b::c::0": 1}"#);
+    }
+
+    #[test]
+    fn brackets_and_backslashes_in_keys() {
+        round_trip(
+            r#"{"x[0]": 1, "x": [2], "y\\z": {"]": 3}}"#,
+            &Keys::default(),
+            r#"{"x\\[0\\]": 1, "x[0]": 2, "y\\\\z.\\]": 3}"#,
+        );
+    }
+
+    #[test]
+    fn empty_keys() {
+        round_trip(r#"{"": {"": 1}, "a": [{"": 2}]}"#, &Keys::default(), r#"{".": 1, "a[0].": 2}"#);
+    }
+
+    #[test]
+    fn unescaped_brackets_are_kept_in_keys() {
+        let value = unflatten(&parse(r#"{"a[b]": 1, "c[0]d": 2, "e[1]": 3}"#), &Keys::default()).unwrap();
+        assert_eq!(
+            json::stringify(&value).unwrap(),
+            json::stringify(&parse(r#"{"a[b]": 1, "c[0]d": 2, "e": [null, 3]}"#)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use crate::errors::{SResult, ToError};
+use crate::json::{self, ParsedValue};
+use crate::ser::{self, FormatOptions};
+use crate::{error, files, string2parsechar, toml, yaml, ParseCharElt};
+use std::collections::HashMap;
+use std::io;
+
+/// A document format that values can be read from or written to, through the
+/// shared [`ParsedValue`] tree.
+pub trait Format {
+    /// Name given to `--from` and `--to`.
+    fn name(&self) -> &str;
+
+    /// File extensions, without the dot, also accepted as names.
+    fn extensions(&self) -> &[&str];
+
+    /// Whether the content looks like this format, to read input without a
+    /// known extension such as stdin.
+    fn sniff(&self, _content: &str) -> bool {
+        false
+    }
+
+    /// How to read the format, `None` for the formats that can only be
+    /// written.
+    fn reader(&self) -> Option<&dyn Reader> {
+        None
+    }
+
+    fn serialize(&self, value: &ParsedValue, opts: &FormatOptions) -> SResult<String>;
+
+    /// Writes a document, to be overridden by the formats that can stream.
+    fn write(&self, value: &ParsedValue, opts: &FormatOptions, out: &mut dyn io::Write) -> SResult<()> {
+        out.write_all(self.serialize(value, opts)?.as_bytes()).cast_error()
+    }
+}
+
+/// Reading side of a [`Format`], whose files are also the ones picked when
+/// walking directories.
+pub trait Reader {
+    /// `path` only appears in the positions of errors.
+    fn parse(&self, path: &str, content: &str) -> SResult<ParsedValue>;
+
+    /// Documents of a file taken as samples, one unless the format holds
+    /// several.
+    fn records(&self, path: &str, content: &str) -> SResult<Vec<ParsedValue>> {
+        Ok(vec![self.parse(path, content)?])
+    }
+
+    /// Positions of the values by JSON Pointer, for the errors about them,
+    /// none by default.
+    fn locate<'path>(&self, _path: &'path str, _content: &str) -> HashMap<String, ParseCharElt<'path>> {
+        HashMap::new()
+    }
+}
+
+pub struct Json;
+
+impl Format for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+
+    fn sniff(&self, content: &str) -> bool {
+        content
+            .trim_start()
+            .starts_with(|ch: char| "{[\"-tfn".contains(ch) || ch.is_ascii_digit())
+    }
+
+    fn reader(&self) -> Option<&dyn Reader> {
+        Some(self)
+    }
+
+    fn serialize(&self, value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+        ser::to_string(value, opts)
+    }
+
+    fn write(&self, value: &ParsedValue, opts: &FormatOptions, out: &mut dyn io::Write) -> SResult<()> {
+        ser::write_io(value, opts, out).map(|_| ())
+    }
+}
+
+impl Reader for Json {
+    fn parse(&self, path: &str, content: &str) -> SResult<ParsedValue> {
+        json::parse(&mut string2parsechar(path, content))
+    }
+
+    fn locate<'path>(&self, path: &'path str, content: &str) -> HashMap<String, ParseCharElt<'path>> {
+        json::locate(&string2parsechar(path, content))
+    }
+}
+
+/// One JSON document per line, read as the array of the records.
+pub struct Ndjson;
+
+impl Format for Ndjson {
+    fn name(&self) -> &str {
+        "ndjson"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ndjson", "jsonl"]
+    }
+
+    fn sniff(&self, content: &str) -> bool {
+        let mut lines = content.lines().map(str::trim).filter(|line| !line.is_empty());
+        lines.clone().count() > 1 && lines.all(|line| line.starts_with(['{', '[']) && line.ends_with(['}', ']']))
+    }
+
+    fn reader(&self) -> Option<&dyn Reader> {
+        Some(self)
+    }
+
+    fn serialize(&self, value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+        let ParsedValue::Array(records) = value else {
+            return Err(error!(
+                "Cannot write NDJSON: it holds one document per line, so the root must be an array, found {}.",
+                value.type_name()
+            ));
+        };
+        let line = FormatOptions {
+            minify: true,
+            final_newline: false,
+            ..*opts
+        };
+        let mut res = records
+            .iter()
+            .map(|record| ser::to_string(record, &line))
+            .collect::<SResult<Vec<_>>>()?
+            .join("\n");
+        if opts.final_newline {
+            res.push('\n');
+        }
+        Ok(res)
+    }
+}
+
+impl Reader for Ndjson {
+    fn parse(&self, path: &str, content: &str) -> SResult<ParsedValue> {
+        self.records(path, content).map(ParsedValue::Array)
+    }
+
+    fn records(&self, path: &str, content: &str) -> SResult<Vec<ParsedValue>> {
+        content
+            .split('\n')
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(nbline, line)| {
+                let mut chars = string2parsechar(path, line);
+                for elt in &mut chars {
+                    elt.line = nbline;
+                }
+                json::parse(&mut chars)
+            })
+            .collect()
+    }
+}
+
+pub struct Yaml;
+
+impl Format for Yaml {
+    fn name(&self) -> &str {
+        "yaml"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["yaml", "yml"]
+    }
+
+    fn serialize(&self, value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+        Ok(yaml::to_string(value, opts))
+    }
+}
+
+pub struct Toml;
+
+impl Format for Toml {
+    fn name(&self) -> &str {
+        "toml"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+
+    fn serialize(&self, value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+        toml::to_string(value, opts)
+    }
+}
+
+/// Formats by name and extension, a later registration taking precedence.
+pub struct Registry {
+    formats: Vec<Box<dyn Format>>,
+}
+
+impl Default for Registry {
+    /// The built-in formats.
+    fn default() -> Self {
+        let mut res = Self::new();
+        res.register(Box::new(Json));
+        res.register(Box::new(Ndjson));
+        res.register(Box::new(Yaml));
+        res.register(Box::new(Toml));
+        res
+    }
+}
+
+impl Registry {
+    /// A registry without any format.
+    pub const fn new() -> Self {
+        Self { formats: vec![] }
+    }
+
+    pub fn register(&mut self, format: Box<dyn Format>) {
+        self.formats.push(format);
+    }
+
+    fn names(&self) -> String {
+        self.formats
+            .iter()
+            .map(|format| format.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Format named by `--from` or `--to`, or by one of its extensions.
+    pub fn by_name(&self, name: &str) -> SResult<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.name() == name || format.extensions().contains(&name))
+            .map(Box::as_ref)
+            .ok_or_else(|| error!("Unknown format {name:?}, expected one of {}.", self.names()))
+    }
+
+    pub fn by_path(&self, path: &str) -> Option<&dyn Format> {
+        let (_, extension) = path.rsplit_once('.')?;
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.extensions().contains(&extension))
+            .map(Box::as_ref)
+    }
+
+    pub fn sniff(&self, content: &str) -> Option<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.sniff(content))
+            .map(Box::as_ref)
+    }
+
+    /// Format of an input: the explicit one, else by extension, else by
+    /// content.
+    pub fn detect(&self, from: Option<&str>, path: &str, content: &str) -> SResult<&dyn Format> {
+        if let Some(name) = from {
+            return self.by_name(name);
+        }
+        self.by_path(path)
+            .or_else(|| self.sniff(content))
+            .ok_or_else(|| error!("Cannot tell the format of {path}, use --from with one of {}.", self.names()))
+    }
+
+    /// Document of an input, in the format given by [`Registry::detect`].
+    pub fn read(&self, from: Option<&str>, path: &str, content: &str) -> SResult<ParsedValue> {
+        Self::reader(self.detect(from, path, content)?)?.parse(files::display(path), content)
+    }
+
+    /// Records of an input, one per line of an NDJSON file.
+    pub fn records(&self, from: Option<&str>, path: &str, content: &str) -> SResult<Vec<ParsedValue>> {
+        Self::reader(self.detect(from, path, content)?)?.records(files::display(path), content)
+    }
+
+    /// Positions of the values of an input, given by its reader.
+    pub fn locate<'path>(
+        &self,
+        from: Option<&str>,
+        path: &'path str,
+        content: &str,
+    ) -> SResult<HashMap<String, ParseCharElt<'path>>> {
+        Ok(Self::reader(self.detect(from, path, content)?)?.locate(files::display(path), content))
+    }
+
+    fn reader(format: &dyn Format) -> SResult<&dyn Reader> {
+        format
+            .reader()
+            .ok_or_else(|| error!("{} can only be written, not read.", format.name()))
+    }
+
+    /// Extensions of the readable formats, as globs for walking directories.
+    pub fn globs(&self) -> Vec<String> {
+        self.formats
+            .iter()
+            .filter(|format| format.reader().is_some())
+            .flat_map(|format| format.extensions().iter().map(|ext| format!("*.{ext}")))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(format: SResult<&dyn Format>) -> String {
+        format.map(|format| format.name().to_owned()).unwrap_or_else(|err| err)
+    }
+
+    #[test]
+    fn ndjson_is_sniffed_before_json() {
+        let formats = Registry::default();
+        let sniffed = |content: &str| formats.sniff(content).map(|format| format.name().to_owned());
+        assert_eq!(sniffed("{\"a\": 1}\n{\"a\": 2}\n").as_deref(), Some("ndjson"));
+        assert_eq!(sniffed("[1]\n\n[2]").as_deref(), Some("ndjson"));
+        assert_eq!(sniffed("{\"a\": 1}\n").as_deref(), Some("json"));
+        assert_eq!(sniffed("{\n    \"a\": [1]\n}\n").as_deref(), Some("json"));
+        assert_eq!(sniffed("  -1.5").as_deref(), Some("json"));
+        assert_eq!(sniffed("a: b"), None);
+    }
+
+    #[test]
+    fn detection() {
+        let formats = Registry::default();
+        assert_eq!(name(formats.detect(Some("yml"), "a.json", "{}")), "yaml");
+        assert_eq!(name(formats.detect(None, "dir/a.jsonl", "{}")), "ndjson");
+        assert_eq!(name(formats.detect(None, "-", "[1]\n[2]")), "ndjson");
+        assert!(name(formats.detect(None, "a.txt", "a: b")).contains("Cannot tell the format of a.txt"));
+        assert!(name(formats.by_name("xml")).contains("Unknown format \"xml\", expected one of json, ndjson, yaml, toml."));
+        assert!(formats.read(None, "a.yaml", "a: b").unwrap_err().contains("yaml can only be written, not read."));
+        assert_eq!(formats.globs(), ["*.json", "*.ndjson", "*.jsonl"]);
+    }
+
+    #[test]
+    fn ndjson_records() {
+        let formats = Registry::default();
+        let records = formats.records(None, "a.ndjson", "{\"a\": 1}\n\n[2]\n").unwrap();
+        assert_eq!(records.len(), 2);
+        let err = formats.records(None, "a.ndjson", "{\"a\": 1}\n{\"a\": 1} x\n").unwrap_err();
+        assert!(err.contains("a.ndjson:1:"), "{err}");
+        let value = formats.read(None, "a.ndjson", "{\"a\": [1, 2]}\n2\n").unwrap();
+        let opts = FormatOptions { final_newline: true, ..FormatOptions::default() };
+        assert_eq!(Ndjson.serialize(&value, &opts).unwrap(), "{\"a\":[1,2]}\n2\n");
+        assert!(Ndjson.serialize(&ParsedValue::null(), &opts).is_err());
+    }
+
+    /// Comma-separated numbers, to check that formats can be added and
+    /// override the built-in ones.
+    struct Numbers;
+
+    impl Format for Numbers {
+        fn name(&self) -> &str {
+            "numbers"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["txt", "json"]
+        }
+
+        fn reader(&self) -> Option<&dyn Reader> {
+            Some(self)
+        }
+
+        fn serialize(&self, value: &ParsedValue, _: &FormatOptions) -> SResult<String> {
+            match value {
+                ParsedValue::Array(arr) => Ok(arr.iter().map(json::stringify).collect::<SResult<Vec<_>>>()?.join(",")),
+                _ => Err(error!("Not a list.")),
+            }
+        }
+    }
+
+    impl Reader for Numbers {
+        fn parse(&self, _: &str, content: &str) -> SResult<ParsedValue> {
+            Ok(ParsedValue::Array(content.split(',').map(|num| ParsedValue::Value(num.trim().to_owned())).collect()))
+        }
+    }
+
+    #[test]
+    fn registered_formats() {
+        let mut formats = Registry::default();
+        formats.register(Box::new(Numbers));
+        let value = formats.read(None, "a.txt", "1, 2").unwrap();
+        assert_eq!(json::stringify(&value).unwrap(), "[\n    1,\n    2\n]");
+        assert_eq!(name(formats.by_path("a.json").ok_or_else(String::new)), "numbers");
+        assert_eq!(name(formats.by_name("json")), "numbers");
+        assert_eq!(formats.by_name("numbers").unwrap().serialize(&value, &FormatOptions::default()).unwrap(), "1,2");
+        assert!(Registry::new().detect(None, "a.json", "{}").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::canonical;
+use crate::json::{self, ParsedValue, ParsedValueRef, Scalar};
+use crate::pointer;
+use crate::sha256::Sha256;
+
+pub type Digest = [u8; 32];
+
+fn scalar_digest(raw: &str) -> Digest {
+    let mut hasher = Sha256::default();
+    match json::to_scalar(raw) {
+        Scalar::Null => hasher.update(b"n"),
+        Scalar::Bool(val) => hasher.update(if val { b"t" } else { b"f" }),
+        Scalar::Number(num) => hasher
+            .update(b"d")
+            .update(canonical::es_number(num).unwrap_or_else(|_| raw.to_owned()).as_bytes()),
+        Scalar::Str(val) => hasher.update(b"s").update(val.as_bytes()),
+    };
+    hasher.finish()
+}
+
+/// Hashes children before their parent, so that objects can combine the
+/// digests of their members in key order rather than in source order.
+fn walk(value: &ParsedValue, path: &str, res: &mut Option<&mut Vec<(String, Digest)>>) -> Digest {
+    let slot = res.as_mut().map(|found| {
+        found.push((path.to_owned(), [0; 32]));
+        found.len().saturating_sub(1)
+    });
+    let digest = match json::to_ref(value) {
+        ParsedValueRef::Value(raw) => scalar_digest(raw),
+        ParsedValueRef::Array(arr) => {
+            let mut hasher = Sha256::default();
+            hasher.update(b"a");
+            for (idx, elt) in arr.iter().enumerate() {
+                hasher.update(&walk(elt, &pointer::push(path, &idx.to_string()), res));
+            }
+            hasher.finish()
+        }
+        ParsedValueRef::Object(obj) => {
+            let mut members = obj
+                .iter()
+                .map(|item| {
+                    let key = json::unescape(&item.key);
+                    let digest = walk(&item.value, &pointer::push(path, &key), res);
+                    (key, digest)
+                })
+                .collect::<Vec<_>>();
+            members.sort();
+            let mut hasher = Sha256::default();
+            hasher.update(b"o");
+            for (key, digest) in members {
+                hasher
+                    .update(&crate::sha256::digest(key.as_bytes()))
+                    .update(&digest);
+            }
+            hasher.finish()
+        }
+    };
+    if let Some((found, idx)) = res.as_mut().zip(slot) {
+        if let Some(entry) = found.get_mut(idx) {
+            entry.1 = digest;
+        }
+    }
+    digest
+}
+
+/// Content digest of a value: formatting, number spelling, string escapes and
+/// key order do not change it.
+pub fn digest(value: &ParsedValue) -> Digest {
+    walk(value, "", &mut None)
+}
+
+/// Digest of every subtree, by JSON Pointer, parents first.
+pub fn subtrees(value: &ParsedValue) -> Vec<(String, Digest)> {
+    let mut res = vec![];
+    walk(value, "", &mut Some(&mut res));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn hash(text: &str) -> Digest {
+        digest(&json::parse(&mut string2parsechar("", text)).unwrap())
+    }
+
+    #[test]
+    fn key_order_is_ignored() {
+        assert_eq!(hash(r#"{"a": 1, "b": {"c": [1, 2], "d": null}}"#), hash(r#"{"b": {"d": null, "c": [1, 2]}, "a": 1}"#));
+    }
+
+    #[test]
+    fn formatting_is_ignored() {
+        assert_eq!(hash("{\"a\":[1,{\"b\":\"x y\"}]}"), hash("{\n    \"a\": [\n        1,\n        { \"b\" : \"x y\" }\n    ]\n}\n"));
+        assert_eq!(hash(r#"{"é": "é\n"}"#), hash(r#"{"\u00e9": "\u00E9\u000a"}"#));
+    }
+
+    #[test]
+    fn number_spelling_is_ignored() {
+        assert_eq!(hash("[1, 100, 0.5, 0]"), hash("[1.0, 1e2, 5E-1, -0]"));
+    }
+
+    #[test]
+    fn content_changes_the_digest() {
+        let base = hash(r#"{"a": [1, 2]}"#);
+        for other in [r#"{"a": [2, 1]}"#, r#"{"b": [1, 2]}"#, r#"{"a": [1, 2, 3]}"#, r#"{"a": ["1", 2]}"#, r#"{"a": {"0": 1, "1": 2}}"#] {
+            assert_ne!(hash(other), base, "{other}");
+        }
+        assert_ne!(hash("null"), hash("\"null\""));
+        assert_ne!(hash("true"), hash("1"));
+    }
+
+    #[test]
+    fn subtrees_match_their_digest() {
+        let value = json::parse(&mut string2parsechar("", r#"{"a": {"b": [true]}, "c": {"b": [true]}}"#)).unwrap();
+        let found = subtrees(&value);
+        let pointers = found.iter().map(|(pointer, _)| pointer.as_str()).collect::<Vec<_>>();
+        assert_eq!(pointers, ["", "/a", "/a/b", "/a/b/0", "/c", "/c/b", "/c/b/0"]);
+        let at = |pointer: &str| found.iter().find(|(found, _)| found == pointer).map(|(_, digest)| *digest);
+        assert_eq!(at(""), Some(digest(&value)));
+        assert_eq!(at("/a"), at("/c"));
+        assert_eq!(at("/a/b/0"), Some(hash("true")));
+    }
+}
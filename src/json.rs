@@ -1,51 +1,57 @@
-use crate::errors::{SResult, ToError};
-use crate::ParseCharElt;
+use crate::errors::SResult;
+use crate::visit::{self, Path, Traverse};
+use crate::{ser, ParseCharElt};
+use core::convert::Infallible;
+use core::ops::ControlFlow;
 use core::fmt::{self, Write};
 use core::hash;
+use core::mem;
+use std::collections::HashMap;
 use std::fs;
 
 macro_rules! define_enum_and_ref {
-    ($name:ident, $nameref:ident, $namerefmut:ident, $($variant:ident($t:ty)),*) => {
+    ($vis:vis $name:ident, $nameref:ident, $namerefmut:ident, $($variant:ident($t:ty) => $visit:ident, $visit_mut:ident, $fold:ident),*) => {
         #[derive(Debug, Clone)]
-        enum $name {
+        $vis enum $name {
             $($variant($t),)*
         }
 
         #[derive(Debug)]
-        enum $nameref<'to_ref> {
+        $vis enum $nameref<'to_ref> {
             $($variant(&'to_ref $t),)*
         }
 
         #[allow(dead_code)]
         #[derive(Debug)]
-        enum $namerefmut<'to_ref> {
+        $vis enum $namerefmut<'to_ref> {
             $($variant(&'to_ref mut $t),)*
         }
 
         #[allow(clippy::pattern_type_mismatch)]
-        const fn to_ref(elt: &$name) -> $nameref {
+        $vis const fn to_ref(elt: &$name) -> $nameref<'_> {
             match elt {
                 $($name::$variant(content) => $nameref::$variant(content),)*
             }
         }
 
         #[allow(clippy::pattern_type_mismatch)]
-        fn to_refmut(elt: &mut $name) -> $namerefmut {
+        $vis fn to_refmut(elt: &mut $name) -> $namerefmut<'_> {
             match elt {
                 $($name::$variant(content) => $namerefmut::$variant(content),)*
             }
         }
 
+        visit::define_traversal!($name, $nameref, $namerefmut, $($variant($t) => $visit, $visit_mut, $fold),*);
     };
 }
 
 define_enum_and_ref!(
-    ParsedValue,
+    pub ParsedValue,
     ParsedValueRef,
     ParsedValueRefMut,
-    Value(String),
-    Array(Vec<ParsedValue>),
-    Object(Parsed)
+    Value(String) => visit_scalar, visit_scalar_mut, fold_scalar,
+    Array(Vec<ParsedValue>) => visit_array, visit_array_mut, fold_array,
+    Object(Parsed) => visit_object, visit_object_mut, fold_object
 );
 
 impl Default for ParsedValue {
@@ -81,16 +87,292 @@ impl ParsedValue {
     }
 
     fn is_empty(&self) -> bool {
+        visit::visit(&mut LastIsEmpty, self).break_value().unwrap_or(true)
+    }
+}
+
+/// Whether the value being parsed last, at the end of the innermost
+/// container, is still empty.
+struct LastIsEmpty;
+
+impl Visitor for LastIsEmpty {
+    type Break = bool;
+
+    fn visit_scalar(&mut self, _: &mut Path, val: &String) -> ControlFlow<bool> {
+        ControlFlow::Break(val.is_empty())
+    }
+
+    fn visit_array(&mut self, path: &mut Path, arr: &Vec<ParsedValue>) -> ControlFlow<bool> {
+        match arr.last() {
+            Some(last) => self.visit(path, last),
+            None => ControlFlow::Break(true),
+        }
+    }
+
+    fn visit_object(&mut self, path: &mut Path, obj: &Parsed) -> ControlFlow<bool> {
+        match obj.last() {
+            Some(last) => self.visit(path, &last.value),
+            None => ControlFlow::Break(true),
+        }
+    }
+}
+
+/// Interpretation of the raw token stored in a [`ParsedValue::Value`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl ParsedValue {
+    pub fn string(val: &str) -> Self {
+        Self::Value(format!("\"{}\"", escape(val)))
+    }
+
+    pub fn number(val: f64) -> Self {
+        Self::Value(format_number(val))
+    }
+
+    pub fn boolean(val: bool) -> Self {
+        Self::Value(val.to_string())
+    }
+
+    pub fn null() -> Self {
+        Self::Value("null".to_owned())
+    }
+
+    pub fn scalar(&self) -> Option<Scalar> {
         match to_ref(self) {
-            ParsedValueRef::Value(val) => val.is_empty(),
-            ParsedValueRef::Array(arr) => arr.last().map_or_else(|| true, Self::is_empty),
-            ParsedValueRef::Object(obj) => obj
-                .last()
-                .map_or_else(|| true, |last| last.value.is_empty()),
+            ParsedValueRef::Value(val) => Some(to_scalar(val)),
+            ParsedValueRef::Array(_) | ParsedValueRef::Object(_) => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.scalar() {
+            None if matches!(self, Self::Array(_)) => "array",
+            None => "object",
+            Some(Scalar::Null) => "null",
+            Some(Scalar::Bool(_)) => "boolean",
+            Some(Scalar::Number(_)) => "number",
+            Some(Scalar::Str(_)) => "string",
+        }
+    }
+
+    /// Structural equality: scalars by meaning, objects regardless of key order.
+    pub fn same(&self, other: &Self) -> bool {
+        match (to_ref(self), to_ref(other)) {
+            (ParsedValueRef::Value(_), ParsedValueRef::Value(_)) => self.scalar() == other.scalar(),
+            (ParsedValueRef::Array(left), ParsedValueRef::Array(right)) => {
+                left.len() == right.len()
+                    && left.iter().zip(right).all(|(lft, rgt)| lft.same(rgt))
+            }
+            (ParsedValueRef::Object(left), ParsedValueRef::Object(right)) => {
+                left.len() == right.len()
+                    && left.iter().all(|item| {
+                        get(right, &item.key).is_some_and(|found| found.same(&item.value))
+                    })
+            }
+            _ => false,
         }
     }
 }
 
+/// Builds a [`ParsedValue`] from JSON-like syntax, Rust expressions being
+/// converted with `ToJson`:
+/// `json!{ "a": [1, true, null, {"b": x}], (key): -2.5 }`.
+#[macro_export]
+macro_rules! json {
+    (@array [$($elems:expr,)*]) => {
+        ::std::vec![$($elems,)*]
+    };
+    (@array [$($elems:expr,)*] , $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)*] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!(null),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!(true),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!(false),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!([$($array)*]),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] {$($object:tt)*} $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!({$($object)*}),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!($next),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::json!(@array [$($elems,)* $crate::json!($last),])
+    };
+
+    (@item ($key:expr) $value:expr) => {
+        $crate::json::ParsingItem {
+            key: $crate::json::escape(&::std::string::ToString::to_string(&$key)),
+            value: $value,
+        }
+    };
+    (@object [$($items:expr,)*]) => {
+        ::std::vec![$($items,)*]
+    };
+    (@object [$($items:expr,)*] , $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)*] $($rest)*)
+    };
+    (@object [$($items:expr,)*] $key:literal : $($rest:tt)*) => {
+        $crate::json!(@value [$($items,)*] ($key) $($rest)*)
+    };
+    (@object [$($items:expr,)*] ($key:expr) : $($rest:tt)*) => {
+        $crate::json!(@value [$($items,)*] ($key) $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) null $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!(null)),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) true $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!(true)),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) false $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!(false)),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) [$($array:tt)*] $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!([$($array)*])),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) {$($object:tt)*} $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!({$($object)*})),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) $value:expr, $($rest:tt)*) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!($value)),] $($rest)*)
+    };
+    (@value [$($items:expr,)*] ($key:expr) $value:expr) => {
+        $crate::json!(@object [$($items,)* $crate::json!(@item ($key) $crate::json!($value)),])
+    };
+
+    (null) => {
+        $crate::json::ParsedValue::null()
+    };
+    (true) => {
+        $crate::json::ParsedValue::boolean(true)
+    };
+    (false) => {
+        $crate::json::ParsedValue::boolean(false)
+    };
+    ([$($array:tt)*]) => {
+        $crate::json::ParsedValue::Array($crate::json!(@array [] $($array)*))
+    };
+    ({$($object:tt)*}) => {
+        $crate::json::ParsedValue::Object($crate::json!(@object [] $($object)*))
+    };
+    ($key:literal : $($rest:tt)*) => {
+        $crate::json!({$key : $($rest)*})
+    };
+    (($key:expr) : $($rest:tt)*) => {
+        $crate::json!({($key) : $($rest)*})
+    };
+    ($other:expr) => {
+        $crate::convert::ToJson::to_json(&$other)
+    };
+}
+
+pub fn to_scalar(raw: &str) -> Scalar {
+    match raw {
+        "null" => Scalar::Null,
+        "true" => Scalar::Bool(true),
+        "false" => Scalar::Bool(false),
+        _ if raw.starts_with('"') => Scalar::Str(unescape(
+            raw.strip_prefix('"')
+                .and_then(|val| val.strip_suffix('"'))
+                .unwrap_or(raw),
+        )),
+        _ => raw
+            .parse::<f64>()
+            .map_or_else(|_| Scalar::Str(raw.to_owned()), Scalar::Number),
+    }
+}
+
+pub fn format_number(val: f64) -> String {
+    if !val.is_finite() {
+        String::from("null")
+    } else if val.fract() == 0.0_f64 && val.abs() < 1e16_f64 {
+        format!("{val:.0}")
+    } else {
+        format!("{val}")
+    }
+}
+
+/// Last item with the given raw key, as a later key overrides an earlier one.
+pub fn get<'obj>(obj: &'obj Parsed, key: &str) -> Option<&'obj ParsedValue> {
+    obj.iter()
+        .rev()
+        .find(|item| item.key == key)
+        .map(|item| &item.value)
+}
+
+/// Escapes a string for use between quotes, keys being stored in this form.
+pub fn escape(val: &str) -> String {
+    let mut res = String::with_capacity(val.len());
+    for ch in val.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{c}' => res.push_str("\\f"),
+            _ if ch.is_control() => {
+                let _ = write!(res, "\\u{:04x}", u32::from(ch));
+            }
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+pub fn unescape(raw: &str) -> String {
+    let mut res = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            res.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('r') => res.push('\r'),
+            Some('t') => res.push('\t'),
+            Some('b') => res.push('\u{8}'),
+            Some('f') => res.push('\u{c}'),
+            Some('u') => {
+                let high = read_hex(&mut chars);
+                let code = if (0xD800..0xDC00).contains(&high) && chars.as_str().starts_with("\\u") {
+                    chars.nth(1);
+                    let low = read_hex(&mut chars);
+                    0x10000_u32
+                        .saturating_add((high.saturating_sub(0xD800)) << 10_u32)
+                        .saturating_add(low.saturating_sub(0xDC00))
+                } else {
+                    high
+                };
+                res.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            Some(other) => res.push(other),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+fn read_hex(chars: &mut core::str::Chars) -> u32 {
+    let hex = chars.by_ref().take(4).collect::<String>();
+    u32::from_str_radix(&hex, 16).unwrap_or(0xFFFD)
+}
+
 #[derive(PartialEq)]
 enum ParsingIndex {
     Key,
@@ -98,9 +380,9 @@ enum ParsingIndex {
 }
 
 #[derive(Default, Clone)]
-struct ParsingItem {
-    key: String,
-    value: ParsedValue,
+pub struct ParsingItem {
+    pub key: String,
+    pub value: ParsedValue,
 }
 
 impl fmt::Debug for ParsingItem {
@@ -136,6 +418,12 @@ impl hash::Hash for ParsingItem {
 }
 
 impl ParsingItem {
+    /// Same key and structurally the same value, where `==` only compares
+    /// the keys.
+    pub fn same_item(&self, other: &Self) -> bool {
+        self.key == other.key && self.value.same(&other.value)
+    }
+
     fn push(&mut self, ch: ParseCharElt, idx: &ParsingIndex) -> SResult<()> {
         match *idx {
             ParsingIndex::Key => self.key.push(ch.ch),
@@ -145,29 +433,46 @@ impl ParsingItem {
     }
 }
 
-type Parsed = Vec<ParsingItem>;
+pub type Parsed = Vec<ParsingItem>;
+
+/// Drops the empty value pushed after a comma, before a container or the
+/// closing bracket takes its place.
+fn pop_placeholder(result: &mut Vec<ParsedValue>) {
+    if matches!(result.last(), Some(ParsedValue::Value(val)) if val.is_empty()) {
+        result.pop();
+    }
+}
 
 fn parse_array(content: &mut Vec<ParseCharElt>) -> SResult<Vec<ParsedValue>> {
     let mut result = Vec::<ParsedValue>::new();
+    let mut instring = false;
+    let mut escaped = false;
     while let Some(elt) = content.pop() {
         match elt.ch {
+            _ if escaped => {
+                escaped = false;
+                result.push_last(elt)?;
+            }
+            '"' => {
+                instring = !instring;
+                result.push_last(elt)?;
+            }
+            '\\' if instring => {
+                escaped = true;
+                result.push_last(elt)?;
+            }
+            _ if instring => result.push_last(elt)?,
             ']' => {
-                if result.last().unwrap_or(&ParsedValue::default()).is_empty() {
-                    result.pop();
-                };
+                pop_placeholder(&mut result);
                 return Ok(result);
             }
             '[' => {
-                if result.last().unwrap_or(&ParsedValue::default()).is_empty() {
-                    result.pop();
-                }
+                pop_placeholder(&mut result);
                 let array = parse_array(content)?;
-                result.extend(array);
+                result.push(ParsedValue::Array(array));
             }
             '{' => {
-                if result.last().unwrap_or(&ParsedValue::default()).is_empty() {
-                    result.pop();
-                }
+                pop_placeholder(&mut result);
                 let obj = parse_json(content)?;
                 result.push(ParsedValue::Object(obj));
             }
@@ -184,14 +489,23 @@ fn parse_json(content: &mut Vec<ParseCharElt>) -> SResult<Parsed> {
     let mut result = Parsed::default();
     let mut current = ParsingItem::default();
     let mut idx = ParsingIndex::Key;
+    let mut escaped = false;
     while let Some(elt) = content.pop() {
         match elt.ch {
+            _ if escaped => {
+                escaped = false;
+                current.push(elt, &idx)?;
+            }
             '"' => {
                 instring = !instring;
                 if idx == ParsingIndex::Value {
                     current.push(elt, &idx)?;
                 }
             }
+            '\\' if instring => {
+                escaped = true;
+                current.push(elt, &idx)?;
+            }
             _ if instring => current.push(elt, &idx)?,
             ':' => idx = ParsingIndex::Value,
             ',' => {
@@ -216,109 +530,505 @@ fn parse_json(content: &mut Vec<ParseCharElt>) -> SResult<Parsed> {
     Ok(result)
 }
 
-pub trait LocalToString {
-    fn to_string(&self, tab: usize, start_indent: bool) -> Result<String, fmt::Error>;
+struct Locator<'main> {
+    chars: Vec<ParseCharElt<'main>>,
+    pos: usize,
+    found: HashMap<String, ParseCharElt<'main>>,
 }
 
-fn int2indent(tab: usize) -> String {
-    " ".repeat(tab.checked_mul(4).unwrap_or(tab))
-}
+impl Locator<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|elt| elt.ch)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek();
+        self.pos = self.pos.saturating_add(1);
+        ch
+    }
 
-impl LocalToString for Parsed {
-    fn to_string(&self, tab: usize, start_indent: bool) -> Result<String, fmt::Error> {
-        let wrap_indent = int2indent(tab);
-        let elt_tab = tab.checked_add(1).unwrap_or(tab);
-        let elt_indent = int2indent(elt_tab);
-        let subelt_tab = tab.checked_add(2).unwrap_or(tab);
-        let mut buffer = String::new();
-        if start_indent {
-            write!(buffer, "\n{wrap_indent}{{\n")?;
-        } else {
-            buffer.push_str("{\n");
-            //     buffer.push('{');
-            //     buffer.push('\n');
+    fn string(&mut self) -> String {
+        let mut raw = String::new();
+        self.bump();
+        while let Some(ch) = self.bump() {
+            match ch {
+                '"' => break,
+                '\\' => {
+                    raw.push(ch);
+                    raw.extend(self.bump());
+                }
+                _ => raw.push(ch),
+            }
         }
-        let mut last = self.len().checked_sub(1).unwrap_or_default();
-        for item in self {
-            match to_ref(&item.value) {
-                ParsedValueRef::Value(val) => {
-                    write!(buffer, "{}\"{}\": {val}", elt_indent, item.key)?;
+        raw
+    }
+
+    fn value(&mut self, path: String) {
+        if let Some(elt) = self.chars.get(self.pos) {
+            self.found.insert(path.clone(), *elt);
+        }
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                while self.peek() == Some('"') {
+                    let key = unescape(&self.string());
+                    self.bump();
+                    self.value(crate::pointer::push(&path, &key));
+                    if self.peek() == Some(',') {
+                        self.bump();
+                    }
                 }
-                ParsedValueRef::Array(arr) => {
-                    write!(
-                        buffer,
-                        "{}\"{}\": {}",
-                        elt_indent,
-                        item.key,
-                        arr.to_string(subelt_tab, false)?,
-                    )?;
+                self.bump();
+            }
+            Some('[') => {
+                self.bump();
+                let mut idx = 0_usize;
+                while !matches!(self.peek(), Some(']') | None) {
+                    self.value(crate::pointer::push(&path, &idx.to_string()));
+                    idx = idx.saturating_add(1);
+                    if self.peek() == Some(',') {
+                        self.bump();
+                    }
                 }
-                ParsedValueRef::Object(obj) => {
-                    write!(buffer, "{}\"{}\": ", elt_indent, item.key)?;
-                    buffer.push_str(&obj.to_string(elt_tab, false)?);
-                    // buffer.push('\n');
+                self.bump();
+            }
+            Some('"') => {
+                self.string();
+            }
+            _ => {
+                while !matches!(self.peek(), Some(',' | ']' | '}') | None) {
+                    self.bump();
                 }
             }
-            if last != 0 {
-                buffer.push_str(",\n");
-                last = last.checked_sub(1).unwrap_or_default();
+        }
+    }
+}
+
+/// Finds where every value starts in the source, by JSON Pointer, so that
+/// errors found on the parsed tree can still be raised at a position.
+pub fn locate<'main>(content: &[ParseCharElt<'main>]) -> HashMap<String, ParseCharElt<'main>> {
+    let mut locator = Locator {
+        chars: content.iter().rev().copied().collect(),
+        pos: 0,
+        found: HashMap::new(),
+    };
+    locator.value(String::new());
+    locator.found
+}
+
+/// Removes the item with an empty key and value that `parse_json` leaves in `{}`.
+struct Prune;
+
+impl VisitorMut for Prune {
+    type Break = Infallible;
+
+    fn visit_object_mut(&mut self, path: &mut Path, obj: &mut Parsed) -> ControlFlow<Infallible> {
+        obj.retain(|item| {
+            !(item.key.is_empty() && matches!(&item.value, ParsedValue::Value(val) if val.is_empty()))
+        });
+        obj.walk_mut(self, path)
+    }
+}
+
+/// Parses a whole document into its root value.
+pub fn parse(content: &mut Vec<ParseCharElt>) -> SResult<ParsedValue> {
+    let mut root = match content.pop() {
+        Some(elt) if elt.ch == '{' => ParsedValue::Object(parse_json(content)?),
+        Some(elt) if elt.ch == '[' => ParsedValue::Array(parse_array(content)?),
+        // A scalar root, which `parse_json` would take for a key.
+        Some(elt) => {
+            let raw = core::iter::once(elt.ch)
+                .chain(content.drain(..).rev().map(|elt| elt.ch))
+                .collect::<String>();
+            if !is_scalar(&raw) {
+                return Err(crate::raise(&format!("Invalid JSON value {raw}"), &elt));
             }
+            return Ok(ParsedValue::Value(raw));
+        }
+        None => return Err("EOF: empty document".to_owned()),
+    };
+    if let Some(elt) = content.last() {
+        return Err(crate::raise("Unexpected text after the document", elt));
+    }
+    let _ = visit::visit_mut(&mut Prune, &mut root);
+    Ok(root)
+}
+
+/// Whether a raw token is a valid JSON literal, number or string.
+fn is_scalar(raw: &str) -> bool {
+    match raw {
+        "null" | "true" | "false" => true,
+        _ if raw.starts_with('"') => {
+            let mut escaped = false;
+            let end = raw.char_indices().skip(1).find(|&(_, ch)| {
+                let found = ch == '"' && !escaped;
+                escaped = ch == '\\' && !escaped;
+                found
+            });
+            end.is_some_and(|(idx, _)| idx.saturating_add(1) == raw.len())
+        }
+        _ => is_number(raw),
+    }
+}
+
+/// Checks the number grammar of RFC 8259, stricter than `f64::from_str`.
+fn is_number(raw: &str) -> bool {
+    let digits = |text: &str| text.len() - text.trim_start_matches(|ch: char| ch.is_ascii_digit()).len();
+    let rest = raw.strip_prefix('-').unwrap_or(raw);
+    let int = digits(rest);
+    if int == 0 || (int > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = rest.get(int..).unwrap_or_default();
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = digits(frac);
+        if len == 0 {
+            return false;
+        }
+        rest = frac.get(len..).unwrap_or_default();
+    }
+    if let Some(exp) = rest.strip_prefix(['e', 'E']) {
+        let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+        let len = digits(exp);
+        if len == 0 {
+            return false;
+        }
+        rest = exp.get(len..).unwrap_or_default();
+    }
+    rest.is_empty()
+}
+
+pub fn stringify(value: &ParsedValue) -> SResult<String> {
+    ser::to_string(value, &ser::FormatOptions::default())
+}
+
+/// How `append` combines the supplement with the previous document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Merge {
+    /// Top-level members replace the ones of the same key or are appended,
+    /// and top-level arrays are concatenated.
+    #[default]
+    Shallow,
+    /// JSON Merge Patch (RFC 7386): objects merge recursively, null deletes.
+    MergePatch,
+    /// Objects merge recursively and arrays are concatenated.
+    DeepConcat,
+    /// Objects merge recursively and arrays are replaced.
+    DeepReplace,
+}
+
+impl Merge {
+    pub fn from_name(name: &str) -> SResult<Self> {
+        match name {
+            "shallow" => Ok(Self::Shallow),
+            "merge-patch" | "patch" => Ok(Self::MergePatch),
+            "deep" | "deep-concat" => Ok(Self::DeepConcat),
+            "deep-replace" => Ok(Self::DeepReplace),
+            _ => Err(crate::error!(
+                "Merge strategy {name} not supported: expected shallow, merge-patch, deep-concat or deep-replace."
+            )),
         }
-        write!(buffer, "\n{wrap_indent}}}")?;
-        Ok(buffer)
     }
 }
 
-impl LocalToString for Vec<ParsedValue> {
-    fn to_string(&self, tab: usize, _: bool) -> Result<String, fmt::Error> {
-        let mut last = self.len().checked_sub(1).unwrap_or_default();
-        let beg = &format!("\n{}", &int2indent(tab));
-        let mut buffer = String::from("[");
-        for item in self {
-            match to_ref(item) {
-                ParsedValueRef::Value(val) => {
-                    buffer.push_str(beg);
-                    buffer.push_str(val);
+fn merge_patch(target: ParsedValue, patch: &ParsedValue) -> ParsedValue {
+    let ParsedValue::Object(items) = patch else {
+        return patch.clone();
+    };
+    let mut res = match target {
+        ParsedValue::Object(obj) => obj,
+        _ => Parsed::new(),
+    };
+    for item in items {
+        let key = unescape(&item.key);
+        if item.value.scalar() == Some(Scalar::Null) {
+            res.retain(|old| unescape(&old.key) != key);
+            continue;
+        }
+        // Updated where the key already is, the duplicates being dropped.
+        match res.iter().position(|old| unescape(&old.key) == key) {
+            Some(idx) => {
+                let mut seen = 0_usize;
+                res.retain(|old| {
+                    let keep = unescape(&old.key) != key || seen == idx;
+                    seen = seen.saturating_add(1);
+                    keep
+                });
+                if let Some(old) = res.get_mut(idx) {
+                    old.value = merge_patch(mem::take(&mut old.value), &item.value);
                 }
-                ParsedValueRef::Array(arr) => {
-                    buffer.push_str(&arr.to_string(tab.checked_add(1).unwrap_or(tab), true)?);
+            }
+            None => res.push(ParsingItem {
+                key: item.key.clone(),
+                value: merge_patch(ParsedValue::null(), &item.value),
+            }),
+        }
+    }
+    ParsedValue::Object(res)
+}
+
+fn shallow_merge(target: ParsedValue, suppl: &ParsedValue) -> SResult<ParsedValue> {
+    match (target, suppl) {
+        (ParsedValue::Object(mut obj), ParsedValue::Object(items)) => {
+            for item in items {
+                let key = unescape(&item.key);
+                match obj.iter_mut().find(|old| unescape(&old.key) == key) {
+                    Some(old) => old.value = item.value.clone(),
+                    None => obj.push(item.clone()),
                 }
+            }
+            Ok(ParsedValue::Object(obj))
+        }
+        (ParsedValue::Array(mut arr), ParsedValue::Array(items)) => {
+            arr.extend(items.iter().cloned());
+            Ok(ParsedValue::Array(arr))
+        }
+        (target, _) => Err(crate::error!(
+            "Cannot append {} to {}: a shallow merge takes two objects or two arrays.",
+            suppl.type_name(),
+            target.type_name()
+        )),
+    }
+}
 
-                ParsedValueRef::Object(obj) => {
-                    buffer.push_str(&obj.to_string(tab, true)?);
+fn deep_merge(target: ParsedValue, suppl: &ParsedValue, concat: bool) -> ParsedValue {
+    match (target, suppl) {
+        (ParsedValue::Object(mut obj), ParsedValue::Object(items)) => {
+            for item in items {
+                let key = unescape(&item.key);
+                match obj.iter_mut().rev().find(|old| unescape(&old.key) == key) {
+                    Some(old) => old.value = deep_merge(mem::take(&mut old.value), &item.value, concat),
+                    None => obj.push(item.clone()),
                 }
-            };
-            if last != 0 {
-                buffer.push(',');
-                last = last.checked_sub(1).unwrap_or_default();
-            } else {
-                buffer.push('\n');
-                buffer.push_str(&int2indent(tab.checked_sub(1).unwrap_or_default()));
-                buffer.push(']');
             }
+            ParsedValue::Object(obj)
+        }
+        (ParsedValue::Array(mut arr), ParsedValue::Array(items)) if concat => {
+            arr.extend(items.iter().cloned());
+            ParsedValue::Array(arr)
         }
-        Ok(buffer)
+        _ => suppl.clone(),
     }
 }
 
-pub fn read(content: &mut Vec<ParseCharElt>) -> SResult<String> {
-    let parsed = parse_json(content)?;
-    // let parsed: Vec<ParsingItem> = vec![];
-    parsed.to_string(0, false).cast_error()
+pub fn append(previous: ParsedValue, suppl: &ParsedValue, merge: Merge) -> SResult<ParsedValue> {
+    match merge {
+        Merge::Shallow => shallow_merge(previous, suppl),
+        Merge::MergePatch => Ok(merge_patch(previous, suppl)),
+        Merge::DeepConcat => Ok(deep_merge(previous, suppl, true)),
+        Merge::DeepReplace => Ok(deep_merge(previous, suppl, false)),
+    }
 }
 
-pub fn append(
-    path: &str,
-    prevcontent: &mut Vec<ParseCharElt>,
-    supplcontent: &mut Vec<ParseCharElt>,
-) -> SResult<String> {
-    let mut previous = parse_json(prevcontent)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(key: &str, text: &str) -> ParsingItem {
+        ParsingItem {
+            key: key.to_owned(),
+            value: parse(&mut crate::string2parsechar("", text)).unwrap(),
+        }
+    }
+
+    #[test]
+    fn item_equality_compares_keys() {
+        assert!(item("a", "1") == item("a", "2"));
+        assert!(item("a", "1") != item("b", "1"));
+    }
+
+    fn parse_text(text: &str) -> ParsedValue {
+        parse(&mut crate::string2parsechar("", text)).unwrap()
+    }
+
+    #[test]
+    fn invalid_roots_are_rejected() {
+        for text in ["abc", "01", "1.", ".5", "-", "1e", "inf", "NaN", "+1", "\"a\"b", "\"a\\\"", "tru"] {
+            assert!(parse(&mut crate::string2parsechar("", text)).is_err(), "{text}");
+        }
+        for text in ["0", "-1.5e+3", "1E2", "null", "true", "\"a\\\"b\"", "\"\""] {
+            assert!(parse(&mut crate::string2parsechar("", text)).is_ok(), "{text}");
+        }
+    }
+
+    #[test]
+    fn trailing_text_is_rejected() {
+        let err = parse(&mut crate::string2parsechar("a.json", "{\"a\": 1}\n x")).unwrap_err();
+        assert!(err.contains("a.json:1:1: Unexpected text after the document"), "{err}");
+        assert!(parse(&mut crate::string2parsechar("", "[1] 2")).is_err());
+        assert!(parse(&mut crate::string2parsechar("", "{} {}")).is_err());
+        assert!(parse(&mut crate::string2parsechar("", "{\"a\": [1]}\n")).is_ok());
+    }
+
+    fn merged(previous: &str, suppl: &str, merge: Merge) -> String {
+        stringify(&append(parse_text(previous), &parse_text(suppl), merge).unwrap()).unwrap()
+    }
+
+    fn check_merge(previous: &str, suppl: &str, merge: Merge, expected: &str) {
+        assert_eq!(merged(previous, suppl, merge), stringify(&parse_text(expected)).unwrap(), "{merge:?}");
+    }
+
+    const PREVIOUS: &str = r#"{"db": {"host": "a", "port": 5432}, "tags": ["x"], "debug": true}"#;
+    const SUPPL: &str = r#"{"db": {"port": 5433}, "tags": ["y"], "debug": null}"#;
+
+    #[test]
+    fn shallow_merges() {
+        check_merge(PREVIOUS, SUPPL, Merge::Shallow, r#"{"db": {"port": 5433}, "tags": ["y"], "debug": null}"#);
+        check_merge("[1]", "[2, 3]", Merge::Shallow, "[1, 2, 3]");
+        check_merge(r#"{"a": 1}"#, r#"{"b": 2}"#, Merge::Shallow, r#"{"a": 1, "b": 2}"#);
+        assert!(append(parse_text("[1]"), &parse_text("{}"), Merge::Shallow).is_err());
+    }
+
+    #[test]
+    fn merge_patches() {
+        check_merge(PREVIOUS, SUPPL, Merge::MergePatch, r#"{"db": {"host": "a", "port": 5433}, "tags": ["y"]}"#);
+        // The examples of RFC 7386, appendix A.
+        let cases = [
+            (r#"{"a": "b"}"#, r#"{"a": "c"}"#, r#"{"a": "c"}"#),
+            (r#"{"a": "b"}"#, r#"{"b": "c"}"#, r#"{"a": "b", "b": "c"}"#),
+            (r#"{"a": "b"}"#, r#"{"a": null}"#, "{}"),
+            (r#"{"a": "b", "b": "c"}"#, r#"{"a": null}"#, r#"{"b": "c"}"#),
+            (r#"{"a": ["b"]}"#, r#"{"a": "c"}"#, r#"{"a": "c"}"#),
+            (r#"{"a": "c"}"#, r#"{"a": ["b"]}"#, r#"{"a": ["b"]}"#),
+            (r#"{"a": {"b": "c"}}"#, r#"{"a": {"b": "d", "c": null}}"#, r#"{"a": {"b": "d"}}"#),
+            (r#"{"a": [{"b": "c"}]}"#, r#"{"a": [1]}"#, r#"{"a": [1]}"#),
+            (r#"["a", "b"]"#, r#"["c", "d"]"#, r#"["c", "d"]"#),
+            (r#"{"a": "b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a": "foo"}"#, "null", "null"),
+            (r#"{"a": "foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e": null}"#, r#"{"a": 1}"#, r#"{"e": null, "a": 1}"#),
+            ("[1, 2]", r#"{"a": "b", "c": null}"#, r#"{"a": "b"}"#),
+            ("{}", r#"{"a": {"bb": {"ccc": null}}}"#, r#"{"a": {"bb": {}}}"#),
+        ];
+        for (previous, suppl, expected) in cases {
+            check_merge(previous, suppl, Merge::MergePatch, expected);
+        }
+    }
+
+    #[test]
+    fn deep_merges() {
+        check_merge(PREVIOUS, SUPPL, Merge::DeepConcat, r#"{"db": {"host": "a", "port": 5433}, "tags": ["x", "y"], "debug": null}"#);
+        check_merge(PREVIOUS, SUPPL, Merge::DeepReplace, r#"{"db": {"host": "a", "port": 5433}, "tags": ["y"], "debug": null}"#);
+        check_merge(r#"{"a": {"b": [1]}}"#, r#"{"a": {"b": [2], "c": 3}}"#, Merge::DeepConcat, r#"{"a": {"b": [1, 2], "c": 3}}"#);
+        check_merge(r#"{"a": {"b": 1}}"#, r#"{"a": 2}"#, Merge::DeepConcat, r#"{"a": 2}"#);
+        check_merge("[1]", "[2]", Merge::DeepReplace, "[2]");
+    }
+
+    #[test]
+    fn merge_names() {
+        assert_eq!(Merge::from_name("patch"), Ok(Merge::MergePatch));
+        assert_eq!(Merge::from_name("deep"), Ok(Merge::DeepConcat));
+        assert!(Merge::from_name("union").is_err());
+    }
+
+    #[test]
+    fn nested_arrays_are_kept() {
+        let ParsedValue::Array(arr) = parse_text("[[1, 2], [], [[3]], 4]") else {
+            panic!("not an array");
+        };
+        assert_eq!(arr.len(), 4);
+        assert!(matches!(arr.first(), Some(ParsedValue::Array(inner)) if inner.len() == 2));
+        assert!(matches!(arr.get(1), Some(ParsedValue::Array(inner)) if inner.is_empty()));
+        assert!(matches!(arr.get(2), Some(ParsedValue::Array(inner)) if matches!(inner.first(), Some(ParsedValue::Array(_)))));
+        let text = stringify(&parse_text("[[1, 2], [], [[3]], 4]")).unwrap();
+        assert!(parse_text(&text).same(&parse_text("[[1, 2], [], [[3]], 4]")));
+    }
+
+    #[test]
+    fn escaped_quotes_stay_in_strings() {
+        let value = parse_text(r#"{"k\"ey": ["a\"], b", "\\"], "v": "x\"y"}"#);
+        let ParsedValue::Object(obj) = &value else {
+            panic!("not an object");
+        };
+        assert_eq!(obj.len(), 2);
+        assert_eq!(obj.first().map(|item| unescape(&item.key)), Some("k\"ey".to_owned()));
+        let Some(ParsedValue::Array(arr)) = obj.first().map(|item| &item.value) else {
+            panic!("not an array");
+        };
+        assert_eq!(
+            arr.iter().map(ParsedValue::scalar).collect::<Vec<_>>(),
+            [Some(Scalar::Str("a\"], b".to_owned())), Some(Scalar::Str("\\".to_owned()))]
+        );
+        assert_eq!(obj.get(1).and_then(|item| item.value.scalar()), Some(Scalar::Str("x\"y".to_owned())));
+    }
+
+    #[test]
+    fn whitespace_is_kept_in_strings_only() {
+        let value = parse_text("{ \"a  b\" :\n [ \" x\ty \" , 1 ] }");
+        let ParsedValue::Object(obj) = &value else {
+            panic!("not an object");
+        };
+        assert_eq!(obj.first().map(|item| item.key.as_str()), Some("a  b"));
+        assert!(value.same(&parse_text(r#"{"a  b": [" x\ty ", 1]}"#)));
+    }
 
-    let suppl = parse_json(supplcontent)?;
-    previous.extend(suppl);
-    let content = previous.to_string(0, false).cast_error()?;
-    Ok(content)
-    // fs::write(path, content).cast_error()?;
-    // Ok(())
-    // Ok(String::new())
+    /// Same value, with the same key order.
+    fn assert_json(value: &ParsedValue, text: &str) {
+        assert_eq!(stringify(value).unwrap(), stringify(&parse_text(text)).unwrap());
+    }
+
+    #[test]
+    fn json_macro_literals() {
+        assert_json(&crate::json!(null), "null");
+        assert_json(&crate::json!([]), "[]");
+        assert_json(&crate::json!({}), "{}");
+        assert_json(
+            &crate::json!({"a": [1, true, null, {"b": "x", "c": [[false], {}]}], "d": -2.5}),
+            r#"{"a": [1, true, null, {"b": "x", "c": [[false], {}]}], "d": -2.5}"#,
+        );
+        assert_json(&crate::json!("a": 1, "b": [2]), r#"{"a": 1, "b": [2]}"#);
+    }
+
+    #[test]
+    fn json_macro_expressions() {
+        let num = 3_u8;
+        let name = "n\"x";
+        assert_json(
+            &crate::json!([num, num + 1, name, Some(2.5), None::<u8>, vec!["v"], (1, "t")]),
+            r#"[3, 4, "n\"x", 2.5, null, ["v"], [1, "t"]]"#,
+        );
+        assert_json(&crate::json!({"k": num * 2, "s": name.to_owned()}), r#"{"k": 6, "s": "n\"x"}"#);
+    }
+
+    #[test]
+    fn json_macro_computed_keys() {
+        let key = "dyn";
+        assert_json(
+            &crate::json!({(key): 1, (format!("{key}2")): {(name_of(2)): [key]}, "lit": 0}),
+            r#"{"dyn": 1, "dyn2": {"k2": ["dyn"]}, "lit": 0}"#,
+        );
+        // Keys are stored escaped.
+        assert_json(&crate::json!({("q\"t"): true}), r#"{"q\"t": true}"#);
+        assert_json(&crate::json!((key): null), r#"{"dyn": null}"#);
+    }
+
+    fn name_of(idx: u8) -> String {
+        format!("k{idx}")
+    }
+
+    #[test]
+    fn json_macro_trailing_commas() {
+        assert_json(&crate::json!([1, 2,]), "[1, 2]");
+        assert_json(&crate::json!({"a": 1, "b": [true,],}), r#"{"a": 1, "b": [true]}"#);
+    }
+
+    #[test]
+    fn json_macro_matches_to_json() {
+        use crate::convert::ToJson;
+        use std::collections::BTreeMap;
+
+        let map = BTreeMap::from([("a".to_owned(), vec![1_i32, -2]), ("b".to_owned(), vec![])]);
+        assert_json(&map.to_json(), &stringify(&crate::json!({"a": [1, -2], "b": []})).unwrap());
+        assert!(crate::json!(map).same(&crate::json!({"b": [], "a": [1, -2]})));
+        let value = parse_text(r#"{"x": [1, {"y": null}]}"#);
+        assert_json(&crate::json!({"wrapped": value.clone()}), r#"{"wrapped": {"x": [1, {"y": null}]}}"#);
+        assert!(crate::json!(value.clone()).same(&value.to_json()));
+    }
+
+    #[test]
+    fn same_item_compares_values() {
+        assert!(item("a", r#"{"x": 1, "y": [1.0]}"#).same_item(&item("a", r#"{"y": [1], "x": 1}"#)));
+        assert!(!item("a", "1").same_item(&item("a", "2")));
+        assert!(!item("a", "1").same_item(&item("b", "1")));
+    }
 }
@@ -0,0 +1,115 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::restriction,
+    clippy::nursery,
+    clippy::cargo
+)]
+#![allow(clippy::missing_docs_in_private_items)]
+#![allow(clippy::implicit_return)]
+#![allow(clippy::question_mark_used)]
+#![allow(clippy::cargo_common_metadata)]
+#![allow(clippy::single_call_fn)]
+#![allow(clippy::std_instead_of_core)]
+#![allow(clippy::blanket_clippy_restriction_lints)]
+#![feature(stmt_expr_attributes)]
+#![allow(clippy::separated_literal_suffix)]
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_inline_in_public_items)]
+#![allow(clippy::exhaustive_structs)]
+#![allow(clippy::exhaustive_enums)]
+#![allow(clippy::must_use_candidate)]
+#![allow(clippy::return_self_not_must_use)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::too_long_first_doc_paragraph)]
+#![allow(dead_code)]
+#![allow(unused)]
+
+// So that `::parsing` paths, which the derives and the generated types use,
+// also resolve within the crate.
+extern crate self as parsing;
+
+pub mod argv;
+pub mod canonical;
+pub mod check;
+pub mod cli;
+pub mod codegen;
+pub mod convert;
+pub mod diff;
+pub mod errors;
+pub mod files;
+pub mod filter;
+pub mod flatten;
+pub mod format;
+pub mod hash;
+pub mod json;
+pub mod patch;
+pub mod pointer;
+pub mod regex;
+pub mod schema;
+pub mod ser;
+pub mod sha256;
+pub mod sort;
+pub mod toml;
+pub mod validate;
+pub mod view;
+pub mod visit;
+pub mod yaml;
+
+use crate::errors::ToError;
+
+use core::fmt;
+
+#[derive(Clone, Copy)]
+pub struct ParseCharElt<'main> {
+    ch: char,
+    path: &'main str,
+    line: usize,
+    col: usize,
+}
+
+impl fmt::Debug for ParseCharElt<'_> {
+    fn fmt(&self, fmter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmter, "{}", self.ch)
+    }
+}
+
+#[must_use]
+pub fn raise(msg: &str, elt: &ParseCharElt) -> String {
+    error!("{}:{}:{}: {}", elt.path, elt.line, elt.col, msg)
+}
+
+/// Characters of a document, without the whitespace outside of strings and
+/// last first, as the parsers pop them.
+#[must_use]
+pub fn string2parsechar<'path>(path: &'path str, content: &str) -> Vec<ParseCharElt<'path>> {
+    let mut instring = false;
+    let mut escaped = false;
+    let mut chars: Vec<ParseCharElt> = content
+        .split('\n')
+        .enumerate()
+        .flat_map(|(nbline, cont)| {
+            cont.chars()
+                .enumerate()
+                .map(move |(nbcol, contcol)| (nbline, nbcol, contcol))
+        })
+        .map(|(line, col, ch)| ParseCharElt {
+            ch,
+            path,
+            line,
+            col,
+        })
+        .filter(|elt| {
+            let keep = instring || !elt.ch.is_whitespace();
+            match elt.ch {
+                _ if escaped => escaped = false,
+                '\\' if instring => escaped = true,
+                '"' => instring = !instring,
+                _ => (),
+            }
+            keep
+        })
+        .collect::<Vec<_>>();
+    chars.reverse();
+    chars
+}
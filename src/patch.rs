@@ -0,0 +1,383 @@
+use crate::errors::SResult;
+use crate::json::{ParsedValue, ParsingItem, Scalar};
+use crate::{error, json, pointer, ParseCharElt};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+enum Operation {
+    Add { path: Vec<String>, value: ParsedValue },
+    Remove { path: Vec<String> },
+    Replace { path: Vec<String>, value: ParsedValue },
+    Move { from: Vec<String>, path: Vec<String> },
+    Copy { from: Vec<String>, path: Vec<String> },
+    Test { path: Vec<String>, value: ParsedValue },
+}
+
+fn field<'obj>(obj: &'obj [ParsingItem], name: &str) -> SResult<&'obj ParsedValue> {
+    pointer::find_key(obj, name)
+        .and_then(|idx| obj.get(idx))
+        .map(|item| &item.value)
+        .ok_or_else(|| format!("missing \"{name}\" member"))
+}
+
+fn path_field(obj: &[ParsingItem], name: &str) -> SResult<Vec<String>> {
+    match field(obj, name)?.scalar() {
+        Some(Scalar::Str(path)) => pointer::parse(&path).map_err(|_| format!("\"{name}\" is not a valid JSON Pointer")),
+        _ => Err(format!("\"{name}\" must be a string")),
+    }
+}
+
+impl Operation {
+    fn from_value(value: &ParsedValue) -> SResult<Self> {
+        let ParsedValue::Object(obj) = value else {
+            return Err(format!("expected an object, found {}", value.type_name()));
+        };
+        let op = match field(obj, "op")?.scalar() {
+            Some(Scalar::Str(op)) => op,
+            _ => return Err("\"op\" must be a string".to_owned()),
+        };
+        let path = path_field(obj, "path")?;
+        Ok(match op.as_str() {
+            "add" => Self::Add {
+                path,
+                value: field(obj, "value")?.clone(),
+            },
+            "remove" => Self::Remove { path },
+            "replace" => Self::Replace {
+                path,
+                value: field(obj, "value")?.clone(),
+            },
+            "move" => Self::Move {
+                from: path_field(obj, "from")?,
+                path,
+            },
+            "copy" => Self::Copy {
+                from: path_field(obj, "from")?,
+                path,
+            },
+            "test" => Self::Test {
+                path,
+                value: field(obj, "value")?.clone(),
+            },
+            _ => return Err(format!("unknown operation {op:?}")),
+        })
+    }
+
+    fn to_value(&self) -> ParsedValue {
+        match self {
+            Self::Add { path, value } => json!({"op": "add", "path": pointer::join(path), "value": value.clone()}),
+            Self::Remove { path } => json!({"op": "remove", "path": pointer::join(path)}),
+            Self::Replace { path, value } => {
+                json!({"op": "replace", "path": pointer::join(path), "value": value.clone()})
+            }
+            Self::Move { from, path } => {
+                json!({"op": "move", "from": pointer::join(from), "path": pointer::join(path)})
+            }
+            Self::Copy { from, path } => {
+                json!({"op": "copy", "from": pointer::join(from), "path": pointer::join(path)})
+            }
+            Self::Test { path, value } => json!({"op": "test", "path": pointer::join(path), "value": value.clone()}),
+        }
+    }
+
+    fn apply(&self, doc: &mut ParsedValue) -> SResult<()> {
+        match self {
+            Self::Add { path, value } => add(doc, path, value.clone()),
+            Self::Remove { path } => remove(doc, path).map(|_| ()),
+            Self::Replace { path, value } => {
+                let target = pointer::get_mut(doc, path)
+                    .ok_or_else(|| format!("path {} does not exist", pointer::join(path)))?;
+                *target = value.clone();
+                Ok(())
+            }
+            Self::Move { from, path } => {
+                if path.len() > from.len() && path.starts_with(from) {
+                    return Err(format!(
+                        "cannot move {} into its own child {}",
+                        pointer::join(from),
+                        pointer::join(path)
+                    ));
+                }
+                let value = remove(doc, from)?;
+                add(doc, path, value)
+            }
+            Self::Copy { from, path } => {
+                let value = pointer::get(doc, from)
+                    .ok_or_else(|| format!("path {} does not exist", pointer::join(from)))?
+                    .clone();
+                add(doc, path, value)
+            }
+            Self::Test { path, value } => match pointer::get(doc, path) {
+                Some(found) if found.same(value) => Ok(()),
+                Some(found) => Err(format!(
+                    "test failed at {}: found {}",
+                    pointer::join(path),
+                    json::stringify(found)?
+                )),
+                None => Err(format!("path {} does not exist", pointer::join(path))),
+            },
+        }
+    }
+}
+
+fn parent<'doc>(doc: &'doc mut ParsedValue, path: &[String]) -> SResult<(&'doc mut ParsedValue, String)> {
+    let (last, init) = path
+        .split_last()
+        .ok_or_else(|| "the root has no parent".to_owned())?;
+    let target = pointer::get_mut(doc, init)
+        .ok_or_else(|| format!("parent {} does not exist", pointer::join(init)))?;
+    Ok((target, last.clone()))
+}
+
+fn add(doc: &mut ParsedValue, path: &[String], value: ParsedValue) -> SResult<()> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (target, last) = parent(doc, path)?;
+    match target {
+        ParsedValue::Object(obj) => {
+            let key = json::escape(&last);
+            match pointer::find_key(obj, &last).and_then(|idx| obj.get_mut(idx)) {
+                Some(item) => item.value = value,
+                None => obj.push(ParsingItem { key, value }),
+            }
+        }
+        ParsedValue::Array(arr) if last == "-" => arr.push(value),
+        ParsedValue::Array(arr) => {
+            let idx = pointer::array_index(&last, arr.len().saturating_add(1))
+                .ok_or_else(|| format!("index {last} is out of bounds of {}", pointer::join(path)))?;
+            arr.insert(idx, value);
+        }
+        ParsedValue::Value(_) => return Err(format!("cannot add a child to the scalar at {}", pointer::join(path))),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut ParsedValue, path: &[String]) -> SResult<ParsedValue> {
+    let (target, last) = parent(doc, path)?;
+    let missing = || format!("path {} does not exist", pointer::join(path));
+    match target {
+        ParsedValue::Object(obj) => {
+            let idx = pointer::find_key(obj, &last).ok_or_else(missing)?;
+            let item = obj.remove(idx);
+            obj.retain(|other| json::unescape(&other.key) != last);
+            Ok(item.value)
+        }
+        ParsedValue::Array(arr) => {
+            let idx = pointer::array_index(&last, arr.len()).ok_or_else(missing)?;
+            Ok(arr.remove(idx))
+        }
+        ParsedValue::Value(_) => Err(missing()),
+    }
+}
+
+/// Error at the position of an operation, which only the formats that
+/// locate their values give.
+fn raise(msg: &str, elt: Option<&ParseCharElt>) -> String {
+    elt.map_or_else(|| error!("{msg}"), |elt| crate::raise(msg, elt))
+}
+
+/// Applies every operation of the patch to a copy of `doc`, so that `doc` is
+/// left untouched when an operation fails.
+fn apply(doc: &ParsedValue, patch: &[(Operation, Option<&ParseCharElt>)]) -> SResult<ParsedValue> {
+    let mut res = doc.clone();
+    for (idx, (op, elt)) in patch.iter().enumerate() {
+        op.apply(&mut res)
+            .map_err(|err| raise(&format!("Patch operation {idx} failed: {err}"), *elt))?;
+    }
+    Ok(res)
+}
+
+fn generate(old: &ParsedValue, new: &ParsedValue, path: &mut Vec<String>, res: &mut Vec<Operation>) {
+    match (old, new) {
+        (ParsedValue::Object(left), ParsedValue::Object(right)) => {
+            for item in left {
+                let key = json::unescape(&item.key);
+                if pointer::find_key(right, &key).is_none() {
+                    path.push(key);
+                    res.push(Operation::Remove { path: path.clone() });
+                    path.pop();
+                }
+            }
+            for item in right {
+                let key = json::unescape(&item.key);
+                path.push(key.clone());
+                match pointer::find_key(left, &key).and_then(|idx| left.get(idx)) {
+                    Some(prev) => generate(&prev.value, &item.value, path, res),
+                    None => res.push(Operation::Add {
+                        path: path.clone(),
+                        value: item.value.clone(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (ParsedValue::Array(left), ParsedValue::Array(right)) => {
+            for (idx, (lft, rgt)) in left.iter().zip(right).enumerate() {
+                path.push(idx.to_string());
+                generate(lft, rgt, path, res);
+                path.pop();
+            }
+            for idx in (right.len()..left.len()).rev() {
+                path.push(idx.to_string());
+                res.push(Operation::Remove { path: path.clone() });
+                path.pop();
+            }
+            for value in right.iter().skip(left.len()) {
+                path.push("-".to_owned());
+                res.push(Operation::Add {
+                    path: path.clone(),
+                    value: value.clone(),
+                });
+                path.pop();
+            }
+        }
+        _ if old.same(new) => (),
+        _ => res.push(Operation::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+    }
+}
+
+/// Computes the patch that turns `old` into `new`.
+pub fn diff(old: &ParsedValue, new: &ParsedValue) -> ParsedValue {
+    let mut res = vec![];
+    generate(old, new, &mut vec![], &mut res);
+    ParsedValue::Array(res.iter().map(Operation::to_value).collect())
+}
+
+/// Applies a patch, `positions` locating its operations in their file.
+pub fn run(
+    doc: &ParsedValue,
+    patch: &ParsedValue,
+    positions: &HashMap<String, ParseCharElt>,
+) -> SResult<ParsedValue> {
+    let ParsedValue::Array(ops) = patch else {
+        return Err(error!("A JSON Patch must be an array of operations."));
+    };
+    let ops = ops
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let elt = positions.get(&format!("/{idx}"));
+            Operation::from_value(value)
+                .map(|op| (op, elt))
+                .map_err(|err| raise(&format!("Invalid patch operation {idx}: {err}"), elt))
+        })
+        .collect::<SResult<Vec<_>>>()?;
+    apply(doc, &ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn patched(doc: &str, patch: &str) -> SResult<String> {
+        let positions = json::locate(&string2parsechar("patch.json", patch));
+        run(&parse(doc), &parse(patch), &positions).map(|value| json::stringify(&value).unwrap())
+    }
+
+    fn check(doc: &str, patch: &str, expected: &str) {
+        assert_eq!(patched(doc, patch).unwrap(), json::stringify(&parse(expected)).unwrap(), "{patch}");
+    }
+
+    #[test]
+    fn add() {
+        check(r#"{"a": 1}"#, r#"[{"op": "add", "path": "/b", "value": [2]}]"#, r#"{"a": 1, "b": [2]}"#);
+        check(r#"{"a": 1}"#, r#"[{"op": "add", "path": "/a", "value": 3}]"#, r#"{"a": 3}"#);
+        check("[1, 3]", r#"[{"op": "add", "path": "/1", "value": 2}]"#, "[1, 2, 3]");
+        check("[1]", r#"[{"op": "add", "path": "/-", "value": 2}]"#, "[1, 2]");
+        check("[1]", r#"[{"op": "add", "path": "/1", "value": 2}]"#, "[1, 2]");
+        check("{}", r#"[{"op": "add", "path": "", "value": [1]}]"#, "[1]");
+        assert!(patched("[1]", r#"[{"op": "add", "path": "/3", "value": 2}]"#).unwrap_err().contains("out of bounds"));
+        assert!(patched("{}", r#"[{"op": "add", "path": "/a/b", "value": 2}]"#).unwrap_err().contains("parent /a"));
+    }
+
+    #[test]
+    fn remove() {
+        check(r#"{"a": 1, "b": 2}"#, r#"[{"op": "remove", "path": "/a"}]"#, r#"{"b": 2}"#);
+        check("[1, 2, 3]", r#"[{"op": "remove", "path": "/1"}]"#, "[1, 3]");
+        assert!(patched("[1]", r#"[{"op": "remove", "path": "/-"}]"#).is_err());
+        assert!(patched("{}", r#"[{"op": "remove", "path": "/a"}]"#).unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn replace() {
+        check(r#"{"a": {"b": 1}}"#, r#"[{"op": "replace", "path": "/a/b", "value": null}]"#, r#"{"a": {"b": null}}"#);
+        check("[1]", r#"[{"op": "replace", "path": "", "value": {}}]"#, "{}");
+        assert!(patched("{}", r#"[{"op": "replace", "path": "/a", "value": 1}]"#).is_err());
+    }
+
+    #[test]
+    fn move_and_copy() {
+        check(
+            r#"{"a": {"b": 1}, "c": []}"#,
+            r#"[{"op": "move", "from": "/a/b", "path": "/c/-"}]"#,
+            r#"{"a": {}, "c": [1]}"#,
+        );
+        check("[1, 2, 3]", r#"[{"op": "move", "from": "/0", "path": "/2"}]"#, "[2, 3, 1]");
+        check(r#"{"a": [1]}"#, r#"[{"op": "copy", "from": "/a", "path": "/b"}]"#, r#"{"a": [1], "b": [1]}"#);
+        assert!(patched(r#"{"a": {}}"#, r#"[{"op": "move", "from": "/a", "path": "/a/b"}]"#)
+            .unwrap_err()
+            .contains("own child"));
+    }
+
+    #[test]
+    fn test_op() {
+        check(r#"{"a": [1, {"b": 2.0}]}"#, r#"[{"op": "test", "path": "/a", "value": [1, {"b": 2}]}]"#, r#"{"a": [1, {"b": 2.0}]}"#);
+        assert!(patched(r#"{"a": 1}"#, r#"[{"op": "test", "path": "/a", "value": "1"}]"#)
+            .unwrap_err()
+            .contains("test failed at /a"));
+    }
+
+    #[test]
+    fn escaped_paths() {
+        check(
+            r#"{"a/b": {"~": 1}}"#,
+            r#"[{"op": "replace", "path": "/a~1b/~0", "value": 2}, {"op": "add", "path": "/~01", "value": 3}]"#,
+            r#"{"a/b": {"~": 2}, "~1": 3}"#,
+        );
+    }
+
+    #[test]
+    fn failing_patches_leave_the_document_untouched() {
+        let doc = parse(r#"{"a": 1}"#);
+        let patch = parse(r#"[{"op": "add", "path": "/b", "value": 2}, {"op": "test", "path": "/a", "value": 2}]"#);
+        assert!(run(&doc, &patch, &HashMap::new()).is_err());
+        assert_eq!(json::stringify(&doc).unwrap(), json::stringify(&parse(r#"{"a": 1}"#)).unwrap());
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        let patch = "[\n  {\"op\": \"add\", \"path\": \"/a\", \"value\": 1},\n  {\"op\": \"test\", \"path\": \"/a\", \"value\": 2}\n]";
+        let err = patched("{}", patch).unwrap_err();
+        assert!(err.contains("patch.json:2:2: Patch operation 1 failed"), "{err}");
+        let err = patched("{}", "[{\"op\": \"nope\", \"path\": \"\"}]").unwrap_err();
+        assert!(err.contains("patch.json:0:1: Invalid patch operation 0: unknown operation \"nope\""), "{err}");
+        assert!(patched("{}", r#"[{"op": "add", "value": 1}]"#).unwrap_err().contains("missing \"path\" member"));
+        assert!(patched("{}", "{}").unwrap_err().contains("must be an array"));
+    }
+
+    #[test]
+    fn diff_then_apply() {
+        let cases = [
+            (r#"{"a": 1, "b": [1, 2, 3], "c": {"d": "x"}}"#, r#"{"b": [1, 5], "c": {"d": "x", "e/f": null}, "g": true}"#),
+            ("[1, [2]]", "[1, [2, 3], 4]"),
+            ("1", r#"{"a": 1}"#),
+            (r#"{"~": []}"#, r#"{"~": [{}]}"#),
+        ];
+        for (old, new) in cases {
+            let patch = diff(&parse(old), &parse(new));
+            let applied = run(&parse(old), &patch, &HashMap::new()).unwrap();
+            assert!(applied.same(&parse(new)), "{old} -> {new}");
+        }
+        assert_eq!(json::stringify(&diff(&parse(r#"{"a": 1.0}"#), &parse(r#"{"a": 1}"#))).unwrap(), "[]");
+    }
+}
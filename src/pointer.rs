@@ -0,0 +1,95 @@
+use crate::errors::SResult;
+use crate::error;
+use crate::json::{self, ParsedValue};
+
+/// Splits a JSON Pointer (RFC 6901) into its unescaped reference tokens.
+pub fn parse(pointer: &str) -> SResult<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    let rest = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| error!("JSON Pointer {pointer:?} must start with '/'."))?;
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+pub fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Appends a token to a pointer.
+pub fn push(pointer: &str, token: &str) -> String {
+    format!("{pointer}/{}", escape_token(token))
+}
+
+pub fn join(tokens: &[String]) -> String {
+    tokens.iter().fold(String::new(), |acc, token| push(&acc, token))
+}
+
+/// Position of the item whose unescaped key is `token`, the last one winning.
+pub fn find_key(obj: &[json::ParsingItem], token: &str) -> Option<usize> {
+    obj.iter().rposition(|item| json::unescape(&item.key) == token)
+}
+
+/// Parses an array index token, `-` being refused as it only makes sense to `add`.
+pub fn array_index(token: &str, len: usize) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse::<usize>().ok().filter(|idx| *idx < len)
+}
+
+pub fn get<'val>(value: &'val ParsedValue, tokens: &[String]) -> Option<&'val ParsedValue> {
+    tokens.iter().try_fold(value, |current, token| match current {
+        ParsedValue::Object(obj) => find_key(obj, token).and_then(|idx| obj.get(idx)).map(|item| &item.value),
+        ParsedValue::Array(arr) => array_index(token, arr.len()).and_then(|idx| arr.get(idx)),
+        ParsedValue::Value(_) => None,
+    })
+}
+
+pub fn get_mut<'val>(value: &'val mut ParsedValue, tokens: &[String]) -> Option<&'val mut ParsedValue> {
+    tokens.iter().try_fold(value, |current, token| match current {
+        ParsedValue::Object(obj) => {
+            let idx = find_key(obj, token)?;
+            obj.get_mut(idx).map(|item| &mut item.value)
+        }
+        ParsedValue::Array(arr) => {
+            let idx = array_index(token, arr.len())?;
+            arr.get_mut(idx)
+        }
+        ParsedValue::Value(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(parse("/a~1b/c~0d/~01").unwrap(), ["a/b", "c~d", "~1"]);
+        assert_eq!(push("/x", "a/b~"), "/x/a~1b~0");
+        let tokens = vec!["a/b".to_owned(), "~".to_owned(), String::new()];
+        assert_eq!(parse(&join(&tokens)).unwrap(), tokens);
+        assert_eq!(parse("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse("/").unwrap(), [""]);
+        assert!(parse("a").is_err());
+    }
+
+    #[test]
+    fn lookups() {
+        let value = json::parse(&mut string2parsechar("", r#"{"a/b": [10, {"~": 1}], "": 2}"#)).unwrap();
+        let at = |pointer: &str| get(&value, &parse(pointer).unwrap()).map(|found| json::stringify(found).unwrap());
+        assert_eq!(at("/a~1b/0").as_deref(), Some("10"));
+        assert_eq!(at("/a~1b/1/~0").as_deref(), Some("1"));
+        assert_eq!(at("/").as_deref(), Some("2"));
+        assert_eq!(at("/a~1b/2"), None);
+        assert_eq!(at("/a~1b/01"), None);
+        assert_eq!(at("/a~1b/-"), None);
+        assert_eq!(at("/a~1b/0/x"), None);
+    }
+}
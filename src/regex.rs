@@ -0,0 +1,393 @@
+//! Backtracking matcher for the subset of ECMA-262 regular expressions used by
+//! JSON Schema `pattern`: literals, `.`, classes, `\d \w \s` and their
+//! negations, anchors, word boundaries, groups, alternation and quantifiers.
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    /// One of `d`, `w`, `s`, uppercase for the negation.
+    Shorthand(char),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Vec<Vec<Node>>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    alts: Vec<Vec<Node>>,
+}
+
+fn shorthand(kind: char, ch: char) -> bool {
+    let found = match kind.to_ascii_lowercase() {
+        'd' => ch.is_ascii_digit(),
+        'w' => ch.is_ascii_alphanumeric() || ch == '_',
+        _ => ch.is_whitespace(),
+    };
+    found != kind.is_ascii_uppercase()
+}
+
+fn is_word(ch: Option<&char>) -> bool {
+    ch.is_some_and(|ch| shorthand('w', *ch))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek();
+        self.pos = self.pos.saturating_add(1);
+        ch
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        let found = self.peek() == Some(ch);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alts = vec![self.sequence()?];
+        while self.eat('|') {
+            alts.push(self.sequence()?);
+        }
+        Ok(alts)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![];
+        while !matches!(self.peek(), Some('|' | ')') | None) {
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn hex(&mut self, len: usize) -> Result<char, String> {
+        let digits = (0..len).filter_map(|_| self.bump()).collect::<String>();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() == len)
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid escape \\{digits}"))
+    }
+
+    /// Escape after the backslash, as a class item when it denotes a set.
+    fn escape(&mut self) -> Result<Result<char, ClassItem>, String> {
+        let ch = self.bump().ok_or_else(|| "trailing backslash".to_owned())?;
+        Ok(Ok(match ch {
+            'd' | 'D' | 'w' | 'W' | 's' | 'S' => return Ok(Err(ClassItem::Shorthand(ch))),
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'f' => '\u{c}',
+            'v' => '\u{b}',
+            '0' => '\0',
+            'x' => self.hex(2)?,
+            'u' => self.hex(4)?,
+            _ => ch,
+        }))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let ch = self.bump().ok_or_else(|| "unexpected end of pattern".to_owned())?;
+        Ok(match ch {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err("lookarounds and named groups are not supported".to_owned());
+                }
+                let alts = self.alternatives()?;
+                if !self.eat(')') {
+                    return Err("unclosed group".to_owned());
+                }
+                Node::Group(alts)
+            }
+            '[' => self.class()?,
+            '\\' if self.peek() == Some('b') || self.peek() == Some('B') => {
+                Node::WordBoundary(self.bump() == Some('b'))
+            }
+            '\\' => match self.escape()? {
+                Ok(lit) => Node::Char(lit),
+                Err(item) => Node::Class {
+                    items: vec![item],
+                    negated: false,
+                },
+            },
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before {ch:?}")),
+            _ => Node::Char(ch),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut items = vec![];
+        loop {
+            let lower = match self.bump() {
+                None => return Err("unclosed character class".to_owned()),
+                Some(']') => break,
+                Some('\\') => match self.escape()? {
+                    Ok(lit) => lit,
+                    Err(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                },
+                Some(lit) => lit,
+            };
+            let is_range = self.peek() == Some('-') && !matches!(self.chars.get(self.pos.saturating_add(1)), Some(']') | None);
+            if !is_range {
+                items.push(ClassItem::Range(lower, lower));
+                continue;
+            }
+            self.bump();
+            let upper = match self.bump() {
+                Some('\\') => self.escape()?.map_err(|_| "invalid class range".to_owned())?,
+                Some(lit) => lit,
+                None => return Err("unclosed character class".to_owned()),
+            };
+            if upper < lower {
+                return Err(format!("invalid class range {lower}-{upper}"));
+            }
+            items.push(ClassItem::Range(lower, upper));
+        }
+        Ok(Node::Class { items, negated })
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.bump();
+        }
+        self.chars.get(start..self.pos)?.iter().collect::<String>().parse().ok()
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`, restoring the position if it is a literal brace.
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let start = self.pos;
+        let res = (|| {
+            self.bump();
+            let min = self.number()?;
+            let max = if self.eat(',') {
+                if self.peek() == Some('}') {
+                    None
+                } else {
+                    Some(self.number()?)
+                }
+            } else {
+                Some(min)
+            };
+            self.eat('}').then_some((min, max))
+        })();
+        if res.is_none() {
+            self.pos = start;
+        }
+        res
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.braces() {
+                Some(bounds) => bounds,
+                None => return Ok(node),
+            },
+            Some(ch @ ('*' | '+' | '?')) => {
+                self.bump();
+                match ch {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid quantifier {{{min},{}}}", max.unwrap_or_default()));
+        }
+        // Laziness does not change whether the text matches.
+        self.eat('?');
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+}
+
+fn match_node(node: &Node, input: &[char], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    let current = input.get(pos);
+    let after = pos.saturating_add(1);
+    match node {
+        Node::Char(ch) => current == Some(ch) && next(after),
+        Node::Any => current.is_some_and(|ch| !matches!(ch, '\n' | '\r')) && next(after),
+        Node::Class { items, negated } => {
+            current.is_some_and(|ch| {
+                items.iter().any(|item| match item {
+                    ClassItem::Range(lower, upper) => lower <= ch && ch <= upper,
+                    ClassItem::Shorthand(kind) => shorthand(*kind, *ch),
+                }) != *negated
+            }) && next(after)
+        }
+        Node::Start => pos == 0 && next(pos),
+        Node::End => pos == input.len() && next(pos),
+        Node::WordBoundary(expected) => {
+            let before = pos.checked_sub(1).and_then(|prev| input.get(prev));
+            (is_word(before) != is_word(current)) == *expected && next(pos)
+        }
+        Node::Group(alts) => alts.iter().any(|alt| match_seq(alt, input, pos, next)),
+        Node::Repeat { node, min, max } => match_repeat(node, (*min, *max), input, pos, 0, next),
+    }
+}
+
+fn match_seq(nodes: &[Node], input: &[char], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    match nodes.split_first() {
+        None => next(pos),
+        Some((first, rest)) => match_node(first, input, pos, &mut |after| match_seq(rest, input, after, next)),
+    }
+}
+
+/// Greedy repetition: an iteration that matches nothing only counts towards
+/// the minimum, so that `(a*)*` terminates.
+fn match_repeat(
+    node: &Node,
+    bounds: (usize, Option<usize>),
+    input: &[char],
+    pos: usize,
+    count: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    let (min, max) = bounds;
+    if max.is_some_and(|max| count >= max) {
+        return next(pos);
+    }
+    let more = match_node(node, input, pos, &mut |after| {
+        (after > pos || count < min) && match_repeat(node, bounds, input, after, count.saturating_add(1), next)
+    });
+    more || (count >= min && next(pos))
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let alts = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched closing parenthesis".to_owned());
+        }
+        Ok(Self { alts })
+    }
+
+    /// Whether the pattern matches anywhere in the text, as patterns are not
+    /// implicitly anchored.
+    pub fn is_match(&self, text: &str) -> bool {
+        let input = text.chars().collect::<Vec<_>>();
+        (0..=input.len()).any(|start| {
+            self.alts
+                .iter()
+                .any(|alt| match_seq(alt, &input, start, &mut |_| true))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn search_is_unanchored() {
+        assert!(matches("b", "abc"));
+        assert!(!matches("d", "abc"));
+        assert!(matches("", ""));
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^ab", "abc"));
+        assert!(!matches("^bc", "abc"));
+        assert!(matches("bc$", "abc"));
+        assert!(!matches("ab$", "abc"));
+        assert!(matches("^abc$", "abc"));
+        assert!(!matches("^abc$", "abcd"));
+        assert!(matches("^$", ""));
+        assert!(matches(r"\bcat\b", "a cat sat"));
+        assert!(!matches(r"\bcat\b", "concatenate"));
+        assert!(matches(r"\Bcat", "concatenate"));
+    }
+
+    #[test]
+    fn alternation() {
+        assert!(matches("^(cat|dog)$", "dog"));
+        assert!(!matches("^(cat|dog)$", "cow"));
+        assert!(matches("^a|b$", "ax"));
+        assert!(matches("^a|b$", "xb"));
+        assert!(!matches("^a|b$", "xa"));
+        assert!(matches("^(a|ab)c$", "abc"));
+        assert!(matches("^(|x)y$", "y"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("^[a-c]+$", "abcab"));
+        assert!(!matches("^[a-c]+$", "abd"));
+        assert!(matches("^[^0-9]+$", "abc"));
+        assert!(!matches("^[^0-9]+$", "a1"));
+        assert!(matches(r"^[\d.]+$", "1.25"));
+        assert!(matches("^[-a]+$", "-a-"));
+        assert!(matches(r"^\d\w\s\D\W\S$", "1a x-z"));
+        assert!(!matches(r"^\d$", "a"));
+        assert!(matches("^.$", "é"));
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert!(matches("^a*$", ""));
+        assert!(matches("^a+b?$", "aaab"));
+        assert!(!matches("^a+$", ""));
+        assert!(matches("^a{2,3}$", "aaa"));
+        assert!(!matches("^a{2,3}$", "aaaa"));
+        assert!(matches("^a{2}$", "aa"));
+        assert!(matches("^a{2,}$", "aaaaa"));
+        assert!(matches("^(ab)+$", "ababab"));
+        assert!(matches("^(a*)*$", "aaa"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches(r"^a\.b$", "a.b"));
+        assert!(!matches(r"^a\.b$", "axb"));
+        assert!(matches(r"^\(\)$", "()"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+        assert!(Regex::new("[a").is_err());
+        assert!(Regex::new("*a").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::convert::push_field as push;
+use crate::json;
+use crate::json::{ParsedValue, ParsedValueRef, Scalar};
+
+/// Largest set of distinct strings reported as an `enum`.
+const ENUM_MAX: usize = 8;
+
+/// Everything observed at one path, over all the samples.
+#[derive(Debug, Clone, Default)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    integer: bool,
+    /// Numbers with a fractional part or an exponent.
+    number: bool,
+    /// Number of strings seen, and their distinct values while few enough.
+    strings: usize,
+    values: Option<Vec<String>>,
+    /// Merged shape of the elements, `None` if only empty arrays were seen.
+    array: Option<Option<Box<Shape>>>,
+    object: Option<Object>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Object {
+    /// Number of objects merged.
+    count: usize,
+    /// Properties in order of first appearance, with the number of objects
+    /// they appeared in.
+    props: Vec<(String, Shape, usize)>,
+}
+
+fn is_integer(raw: &str) -> bool {
+    !raw.contains(['.', 'e', 'E'])
+}
+
+impl Shape {
+    fn add(&mut self, value: &ParsedValue) {
+        match json::to_ref(value) {
+            ParsedValueRef::Value(raw) => match json::to_scalar(raw) {
+                Scalar::Null => self.null = true,
+                Scalar::Bool(_) => self.boolean = true,
+                Scalar::Number(_) if is_integer(raw) => self.integer = true,
+                Scalar::Number(_) => self.number = true,
+                Scalar::Str(val) => self.add_string(val),
+            },
+            ParsedValueRef::Array(arr) => {
+                let items = self.array.get_or_insert(None);
+                for elt in arr {
+                    items.get_or_insert_with(Box::default).add(elt);
+                }
+            }
+            ParsedValueRef::Object(obj) => {
+                let object = self.object.get_or_insert_with(Object::default);
+                object.count = object.count.saturating_add(1);
+                let mut seen = vec![];
+                for item in obj {
+                    let key = json::unescape(&item.key);
+                    let idx = match object.props.iter().position(|prop| prop.0 == key) {
+                        Some(idx) => idx,
+                        None => {
+                            object.props.push((key.clone(), Shape::default(), 0));
+                            object.props.len().saturating_sub(1)
+                        }
+                    };
+                    if let Some(prop) = object.props.get_mut(idx) {
+                        prop.1.add(&item.value);
+                        if !seen.contains(&idx) {
+                            prop.2 = prop.2.saturating_add(1);
+                            seen.push(idx);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_string(&mut self, val: String) {
+        self.strings = self.strings.saturating_add(1);
+        if self.strings == 1 {
+            self.values = Some(vec![]);
+        }
+        if let Some(values) = &mut self.values {
+            if !values.contains(&val) {
+                values.push(val);
+            }
+            if values.len() > ENUM_MAX {
+                self.values = None;
+            }
+        }
+    }
+
+    fn types(&self) -> Vec<&'static str> {
+        [
+            (self.object.is_some(), "object"),
+            (self.array.is_some(), "array"),
+            (self.strings > 0, "string"),
+            (self.number, "number"),
+            (self.integer && !self.number, "integer"),
+            (self.boolean, "boolean"),
+            (self.null, "null"),
+        ]
+        .into_iter()
+        .filter_map(|(seen, name)| seen.then_some(name))
+        .collect()
+    }
+
+    fn to_schema(&self) -> ParsedValue {
+        let mut res = vec![];
+        let types = self.types();
+        match types.as_slice() {
+            [] => (),
+            [single] => push(&mut res, "type", json!(single)),
+            _ => push(&mut res, "type", json!(types)),
+        }
+        // A value that was never repeated gives no evidence of an enumeration,
+        // and an enum would reject the other types seen at this path.
+        let only_strings = types.iter().all(|name| matches!(*name, "string" | "null"));
+        if let Some(values) = self.values.as_ref().filter(|values| only_strings && values.len() < self.strings) {
+            let mut values = values.iter().map(|val| json!(val)).collect::<Vec<_>>();
+            if self.null {
+                values.push(ParsedValue::null());
+            }
+            push(&mut res, "enum", ParsedValue::Array(values));
+        }
+        if let Some(Some(items)) = &self.array {
+            push(&mut res, "items", items.to_schema());
+        }
+        if let Some(object) = &self.object {
+            let props = object
+                .props
+                .iter()
+                .map(|(key, shape, _)| json::ParsingItem {
+                    key: json::escape(key),
+                    value: shape.to_schema(),
+                })
+                .collect::<Vec<_>>();
+            let required = object
+                .props
+                .iter()
+                .filter(|prop| prop.2 == object.count)
+                .map(|prop| json!(prop.0))
+                .collect::<Vec<_>>();
+            if !props.is_empty() {
+                push(&mut res, "properties", ParsedValue::Object(props));
+            }
+            if !required.is_empty() {
+                push(&mut res, "required", ParsedValue::Array(required));
+            }
+        }
+        ParsedValue::Object(res)
+    }
+}
+
+/// Infers a JSON Schema (draft 2020-12) describing every sample: a property is
+/// required when present in every object seen at its path, and strings that
+/// repeat among a few distinct values become an `enum`.
+pub fn infer(samples: &[ParsedValue]) -> ParsedValue {
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample);
+    }
+    let mut res = vec![];
+    push(&mut res, "$schema", json!("https://json-schema.org/draft/2020-12/schema"));
+    if let ParsedValue::Object(items) = shape.to_schema() {
+        res.extend(items);
+    }
+    ParsedValue::Object(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+    use crate::validate;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn inferred(samples: &[&str]) -> ParsedValue {
+        infer(&samples.iter().map(|text| parse(text)).collect::<Vec<_>>())
+    }
+
+    fn check(samples: &[&str], expected: &str) {
+        let schema = inferred(samples);
+        let expected = format!(r#"{{"$schema": "https://json-schema.org/draft/2020-12/schema", {}"#, expected.trim_start_matches('{'));
+        assert_eq!(json::stringify(&schema).unwrap(), json::stringify(&parse(&expected)).unwrap());
+        for sample in samples {
+            assert!(validate::validate(&schema, &parse(sample)).unwrap().is_empty(), "{sample}");
+        }
+    }
+
+    #[test]
+    fn scalars() {
+        check(&["1", "2"], r#"{"type": "integer"}"#);
+        check(&["1", "2.5"], r#"{"type": "number"}"#);
+        check(&["true", "null"], r#"{"type": ["boolean", "null"]}"#);
+        check(&["\"a\"", "1"], r#"{"type": ["string", "integer"]}"#);
+    }
+
+    #[test]
+    fn required_and_optional_properties() {
+        check(
+            &[r#"{"id": 1, "name": "a"}"#, r#"{"id": 2, "tag": null}"#],
+            r#"{"type": "object", "properties": {"id": {"type": "integer"}, "name": {"type": "string"}, "tag": {"type": "null"}}, "required": ["id"]}"#,
+        );
+    }
+
+    #[test]
+    fn nullable_fields() {
+        check(
+            &[r#"{"a": 1}"#, r#"{"a": null}"#],
+            r#"{"type": "object", "properties": {"a": {"type": ["integer", "null"]}}, "required": ["a"]}"#,
+        );
+    }
+
+    #[test]
+    fn enums() {
+        check(
+            &[r#"["on", "off", "on"]"#, r#"[null]"#],
+            r#"{"type": "array", "items": {"type": ["string", "null"], "enum": ["on", "off", null]}}"#,
+        );
+        // Strings never repeated, too many of them or mixed with numbers.
+        check(&[r#"["a", "b"]"#], r#"{"type": "array", "items": {"type": "string"}}"#);
+        let many = r#"["a", "b", "c", "d", "e", "f", "g", "h", "i", "a"]"#;
+        check(&[many], r#"{"type": "array", "items": {"type": "string"}}"#);
+        check(&[r#"["a", "a", 1]"#], r#"{"type": "array", "items": {"type": ["string", "integer"]}}"#);
+    }
+
+    #[test]
+    fn arrays() {
+        check(&["[]"], r#"{"type": "array"}"#);
+        check(
+            &["[]", r#"[{"x": 1}, {"x": 2, "y": [1.5]}]"#],
+            r#"{"type": "array", "items": {"type": "object", "properties": {"x": {"type": "integer"}, "y": {"type": "array", "items": {"type": "number"}}}, "required": ["x"]}}"#,
+        );
+    }
+
+    #[test]
+    fn escaped_keys() {
+        check(
+            &[r#"{"a\"b": 1}"#],
+            r#"{"type": "object", "properties": {"a\"b": {"type": "integer"}}, "required": ["a\"b"]}"#,
+        );
+    }
+}
@@ -0,0 +1,466 @@
+use crate::errors::{get_code_color, SResult, ToError};
+use crate::json::{Parsed, ParsedValue};
+use crate::visit::{self, Path, PathElt, Visitor};
+use core::fmt::{self, Write as _};
+use core::ops::ControlFlow;
+use std::borrow::Cow;
+use std::io;
+
+/// Palette of the highlighted output, as `errors::get_code_color` names.
+const KEY: &str = "b";
+const STRING: &str = "g";
+const NUMBER: &str = "c";
+const BOOLEAN: &str = "y";
+const NULL: &str = "m";
+const PUNCTUATION: &str = "w";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+/// Layout of the serializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub indent: Indent,
+    pub space_after_colon: bool,
+    /// Arrays and objects holding only scalars are kept on one line when it
+    /// is at most this wide, 0 disabling it.
+    pub inline_width: usize,
+    pub final_newline: bool,
+    pub line_ending: LineEnding,
+    /// No insignificant whitespace at all, overriding the layout above.
+    pub minify: bool,
+    /// Terminal colors for keys, strings, numbers, booleans, null and
+    /// punctuation.
+    pub color: bool,
+    /// Non-ASCII characters written as `\uXXXX`, astral ones as surrogate
+    /// pairs.
+    pub ascii: bool,
+    /// `<`, `>`, `&`, U+2028 and U+2029 escaped, to embed the output in a
+    /// `<script>` tag.
+    pub html_safe: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: Indent::Spaces(4),
+            space_after_colon: true,
+            inline_width: 0,
+            final_newline: false,
+            line_ending: LineEnding::Lf,
+            minify: false,
+            color: false,
+            ascii: false,
+            html_safe: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Options to reformat `content` with, in place or to check it: without
+    /// colors, and keeping the final newline of the file unless one is asked
+    /// for, as stdout always ends with one.
+    pub fn for_file(&self, content: &str) -> Self {
+        Self {
+            color: false,
+            final_newline: self.final_newline || content.ends_with('\n'),
+            ..*self
+        }
+    }
+}
+
+/// Destination of the serializer.
+pub trait Output {
+    fn put(&mut self, text: &str) -> SResult<()>;
+}
+
+/// Adapts an [`io::Write`], to be buffered by the caller if needed.
+pub struct Io<W>(pub W);
+
+impl<W: io::Write> Output for Io<W> {
+    fn put(&mut self, text: &str) -> SResult<()> {
+        self.0.write_all(text.as_bytes()).cast_error()
+    }
+}
+
+/// Adapts a [`fmt::Write`], such as a `String`.
+pub struct Fmt<W>(pub W);
+
+impl<W: fmt::Write> Output for Fmt<W> {
+    fn put(&mut self, text: &str) -> SResult<()> {
+        self.0.write_str(text).cast_error()
+    }
+}
+
+/// Writes a tree in one pass, without building the text of its children.
+pub struct Serializer<'opts, O> {
+    out: O,
+    opts: &'opts FormatOptions,
+    unit: String,
+}
+
+impl<'opts, O: Output> Serializer<'opts, O> {
+    pub fn new(out: O, opts: &'opts FormatOptions) -> Self {
+        let unit = match opts.indent {
+            Indent::Spaces(width) => " ".repeat(width),
+            Indent::Tabs => "\t".to_owned(),
+        };
+        Self { out, opts, unit }
+    }
+
+    fn newline(&mut self, depth: usize) -> SResult<()> {
+        self.out.put(match self.opts.line_ending {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        })?;
+        for _ in 0..depth {
+            self.out.put(&self.unit)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the escaping options to the raw text of a string or key, where
+    /// every character they touch is a literal one.
+    fn escape<'text>(&self, text: &'text str) -> Cow<'text, str> {
+        let needs = |ch: char| {
+            (self.opts.ascii && !ch.is_ascii())
+                || (self.opts.html_safe && matches!(ch, '<' | '>' | '&' | '\u{2028}' | '\u{2029}'))
+        };
+        if !text.contains(needs) {
+            return Cow::Borrowed(text);
+        }
+        let mut res = String::with_capacity(text.len());
+        for ch in text.chars() {
+            if needs(ch) {
+                for unit in ch.encode_utf16(&mut [0; 2]) {
+                    let _ = write!(res, "\\u{unit:04x}");
+                }
+            } else {
+                res.push(ch);
+            }
+        }
+        Cow::Owned(res)
+    }
+
+    /// Writes a token, highlighted if colors are on.
+    fn token(&mut self, color: &str, text: &str) -> SResult<()> {
+        if !self.opts.color {
+            return self.out.put(text);
+        }
+        self.out.put(&get_code_color(color))?;
+        self.out.put(text)?;
+        self.out.put("\x1b[0m")
+    }
+
+    fn scalar(&mut self, raw: &str) -> SResult<()> {
+        let color = match raw {
+            "null" => NULL,
+            "true" | "false" => BOOLEAN,
+            _ if raw.starts_with('"') => STRING,
+            _ => NUMBER,
+        };
+        let text = self.escape(raw);
+        self.token(color, &text)
+    }
+
+    fn colon(&self) -> &'static str {
+        if self.opts.space_after_colon && !self.opts.minify {
+            ": "
+        } else {
+            ":"
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        if self.opts.minify {
+            ","
+        } else {
+            ", "
+        }
+    }
+
+    /// Width of the one-line form of a container of scalars.
+    fn inline_width(&self, children: &[Child<'_>]) -> Option<usize> {
+        let content = children.iter().try_fold(0_usize, |acc, &(key, elt)| {
+            let ParsedValue::Value(val) = elt else {
+                return None;
+            };
+            let key = key.map_or(0, |key| {
+                self.escape(key)
+                    .chars()
+                    .count()
+                    .saturating_add(self.colon().len())
+                    .saturating_add(2)
+            });
+            Some(acc.saturating_add(key).saturating_add(self.escape(val).chars().count()))
+        })?;
+        Some(
+            content
+                .saturating_add(2)
+                .saturating_add(children.len().saturating_sub(1).saturating_mul(self.separator().len())),
+        )
+    }
+
+    fn key(&mut self, key: &str) -> SResult<()> {
+        if self.opts.color {
+            self.out.put(&get_code_color(KEY))?;
+        }
+        let key = self.escape(key);
+        self.out.put("\"")?;
+        self.out.put(&key)?;
+        self.out.put("\"")?;
+        if self.opts.color {
+            self.out.put("\x1b[0m")?;
+        }
+        let colon = self.colon();
+        self.token(PUNCTUATION, colon)
+    }
+
+    /// Writes an array or an object, whose first line is already indented at
+    /// the depth of `path`.
+    fn container(&mut self, path: &mut Path, brackets: (&str, &str), children: &[Child<'_>]) -> SResult<()> {
+        let (open, close) = brackets;
+        self.token(PUNCTUATION, open)?;
+        if children.is_empty() {
+            return self.token(PUNCTUATION, close);
+        }
+        let inline = self.opts.minify
+            || (self.opts.inline_width > 0
+                && self
+                    .inline_width(children)
+                    .is_some_and(|width| width <= self.opts.inline_width));
+        let depth = path.0.len();
+        for (idx, &(key, elt)) in children.iter().enumerate() {
+            if idx > 0 {
+                let separator = if inline { self.separator() } else { "," };
+                self.token(PUNCTUATION, separator)?;
+            }
+            if !inline {
+                self.newline(depth.saturating_add(1))?;
+            }
+            if let Some(key) = key {
+                self.key(key)?;
+                path.push(PathElt::Key(crate::json::unescape(key)));
+            } else {
+                path.push(PathElt::Index(idx));
+            }
+            let flow = self.visit(path, elt);
+            path.pop();
+            if let ControlFlow::Break(err) = flow {
+                return Err(err);
+            }
+        }
+        if !inline {
+            self.newline(depth)?;
+        }
+        self.token(PUNCTUATION, close)
+    }
+
+    /// Writes a whole document, and returns the output back.
+    pub fn document(mut self, value: &ParsedValue) -> SResult<O> {
+        if let ControlFlow::Break(err) = visit::visit(&mut self, value) {
+            return Err(err);
+        }
+        if self.opts.final_newline {
+            self.newline(0)?;
+        }
+        Ok(self.out)
+    }
+}
+
+/// A member of an array or an object: its raw key, if any, and its value.
+type Child<'val> = (Option<&'val str>, &'val ParsedValue);
+
+fn flow(res: SResult<()>) -> ControlFlow<String> {
+    match res {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(err) => ControlFlow::Break(err),
+    }
+}
+
+impl<O: Output> Visitor for Serializer<'_, O> {
+    type Break = String;
+
+    fn visit_scalar(&mut self, _: &mut Path, raw: &String) -> ControlFlow<String> {
+        flow(self.scalar(raw))
+    }
+
+    fn visit_array(&mut self, path: &mut Path, arr: &Vec<ParsedValue>) -> ControlFlow<String> {
+        let children = arr.iter().map(|elt| (None, elt)).collect::<Vec<_>>();
+        flow(self.container(path, ("[", "]"), &children))
+    }
+
+    fn visit_object(&mut self, path: &mut Path, obj: &Parsed) -> ControlFlow<String> {
+        let children = obj
+            .iter()
+            .map(|item| (Some(item.key.as_str()), &item.value))
+            .collect::<Vec<_>>();
+        flow(self.container(path, ("{", "}"), &children))
+    }
+}
+
+pub fn write_io<W: io::Write>(value: &ParsedValue, opts: &FormatOptions, writer: W) -> SResult<W> {
+    Serializer::new(Io(writer), opts).document(value).map(|out| out.0)
+}
+
+pub fn write_fmt<W: fmt::Write>(value: &ParsedValue, opts: &FormatOptions, writer: W) -> SResult<W> {
+    Serializer::new(Fmt(writer), opts).document(value).map(|out| out.0)
+}
+
+pub fn to_string(value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+    write_fmt(value, opts, String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn format(text: &str, opts: FormatOptions) -> String {
+        to_string(&parse(text), &opts).unwrap()
+    }
+
+    #[test]
+    fn indents() {
+        let text = r#"{"a": [1, {"b": null}]}"#;
+        assert_eq!(
+            format(text, FormatOptions::default()),
+            "{\n    \"a\": [\n        1,\n        {\n            \"b\": null\n        }\n    ]\n}"
+        );
+        let opts = FormatOptions { indent: Indent::Spaces(2), space_after_colon: false, ..FormatOptions::default() };
+        assert_eq!(format(text, opts), "{\n  \"a\":[\n    1,\n    {\n      \"b\":null\n    }\n  ]\n}");
+        let opts = FormatOptions { indent: Indent::Tabs, ..FormatOptions::default() };
+        assert_eq!(format(r#"{"a": [1]}"#, opts), "{\n\t\"a\": [\n\t\t1\n\t]\n}");
+    }
+
+    #[test]
+    fn inline_widths() {
+        let opts = FormatOptions { inline_width: 20, ..FormatOptions::default() };
+        assert_eq!(format(r#"{"a": [1, 2, 3], "b": {"c": "d"}}"#, opts), "{\n    \"a\": [1, 2, 3],\n    \"b\": {\"c\": \"d\"}\n}");
+        // `[1, 2, 3, 4, 5, 6, 7]` is 21 characters wide.
+        assert_eq!(format("[1, 2, 3, 4, 5, 6, 7]", opts), "[\n    1,\n    2,\n    3,\n    4,\n    5,\n    6,\n    7\n]");
+        assert_eq!(format("[1, 2, 3, 4, 5, 6]", opts), "[1, 2, 3, 4, 5, 6]");
+        assert_eq!(format("[[1], 2]", opts), "[\n    [1],\n    2\n]");
+    }
+
+    #[test]
+    fn line_endings() {
+        let opts = FormatOptions { line_ending: LineEnding::CrLf, final_newline: true, ..FormatOptions::default() };
+        assert_eq!(format(r#"{"a": [1]}"#, opts), "{\r\n    \"a\": [\r\n        1\r\n    ]\r\n}\r\n");
+        let opts = FormatOptions { final_newline: true, ..FormatOptions::default() };
+        assert_eq!(format("1", opts), "1\n");
+    }
+
+    #[test]
+    fn minified() {
+        let opts = FormatOptions { minify: true, inline_width: 5, ..FormatOptions::default() };
+        let text = "{\n  \"a b\" : [ 1, {\"c\": \" x \"}, [], {} ],\n  \"d\": null\n}";
+        let minified = format(text, opts);
+        assert_eq!(minified, r#"{"a b":[1,{"c":" x "},[],{}],"d":null}"#);
+        assert!(parse(&minified).same(&parse(text)));
+        let opts = FormatOptions { final_newline: true, ..opts };
+        assert_eq!(format("[1, 2]", opts), "[1,2]\n");
+    }
+
+    #[test]
+    fn empty_containers() {
+        assert_eq!(format(r#"{"a": [], "b": {}, "c": [[]]}"#, FormatOptions::default()), "{\n    \"a\": [],\n    \"b\": {},\n    \"c\": [\n        []\n    ]\n}");
+        assert_eq!(format("[]", FormatOptions::default()), "[]");
+        assert_eq!(format("{}", FormatOptions::default()), "{}");
+    }
+
+    #[test]
+    fn writers() {
+        let value = parse(r#"{"a": ["é", 1]}"#);
+        let opts = FormatOptions { final_newline: true, ..FormatOptions::default() };
+        let bytes = write_io(&value, &opts, Vec::new()).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), to_string(&value, &opts).unwrap());
+        let prefixed = write_fmt(&value, &opts, String::from("> ")).unwrap();
+        assert_eq!(prefixed, format!("> {}", to_string(&value, &opts).unwrap()));
+    }
+
+    #[test]
+    fn deep_documents() {
+        let depth = 200;
+        let text = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let opts = FormatOptions { minify: true, ..FormatOptions::default() };
+        assert_eq!(format(&text, opts), text);
+    }
+
+    #[test]
+    fn colors() {
+        let opts = FormatOptions { color: true, minify: true, ..FormatOptions::default() };
+        let paint = |color: &str, text: &str| format!("{}{text}\x1b[0m", get_code_color(color));
+        let expected = [
+            paint(PUNCTUATION, "{"),
+            paint(KEY, "\"k\""),
+            paint(PUNCTUATION, ":"),
+            paint(PUNCTUATION, "["),
+            paint(STRING, "\"s\""),
+            paint(PUNCTUATION, ","),
+            paint(NUMBER, "-1.5"),
+            paint(PUNCTUATION, ","),
+            paint(BOOLEAN, "true"),
+            paint(PUNCTUATION, ","),
+            paint(NULL, "null"),
+            paint(PUNCTUATION, "]"),
+            paint(PUNCTUATION, "}"),
+        ];
+        assert_eq!(format(r#"{"k": ["s", -1.5, true, null]}"#, opts), expected.concat());
+        let plain = FormatOptions { minify: true, ..FormatOptions::default() };
+        assert!(!format(r#"{"k": ["s"]}"#, plain).contains('\x1b'));
+    }
+
+    #[test]
+    fn ascii() {
+        let opts = FormatOptions { ascii: true, minify: true, ..FormatOptions::default() };
+        assert_eq!(
+            format(r#"{"clé": ["€", "😀", "a\n"]}"#, opts),
+            r#"{"cl\u00e9":["\u20ac","\ud83d\ude00","a\n"]}"#
+        );
+        let escaped = format(r#"["😀"]"#, opts);
+        assert!(escaped.is_ascii());
+        assert_eq!(json::to_scalar(escaped.trim_matches(['[', ']'])), json::Scalar::Str("😀".to_owned()));
+    }
+
+    #[test]
+    fn html_safe() {
+        let opts = FormatOptions { html_safe: true, minify: true, ..FormatOptions::default() };
+        assert_eq!(
+            format("{\"</script>\": \"a & b\u{2028}\u{2029}é\"}", opts),
+            r#"{"\u003c/script\u003e":"a \u0026 b\u2028\u2029é"}"#
+        );
+        let both = FormatOptions { ascii: true, ..opts };
+        assert_eq!(format(r#"["<é>"]"#, both), r#"["\u003c\u00e9\u003e"]"#);
+    }
+
+    #[test]
+    fn files_keep_their_final_newline() {
+        let opts = FormatOptions::default();
+        let value = parse(r#"{"a": [1, 2]}"#);
+        let bare = to_string(&value, &opts).unwrap();
+        // What stdout prints, redirected into a file.
+        let printed = format!("{bare}\n");
+        assert_eq!(to_string(&parse(&printed), &opts.for_file(&printed)).unwrap(), printed);
+        assert_eq!(to_string(&parse(&bare), &opts.for_file(&bare)).unwrap(), bare);
+        let forced = FormatOptions { final_newline: true, ..opts };
+        assert_eq!(to_string(&value, &forced.for_file(&bare)).unwrap(), printed);
+        let colored = FormatOptions { color: true, ..opts };
+        assert!(!colored.for_file(&bare).color);
+    }
+}
@@ -0,0 +1,165 @@
+use core::fmt::Write as _;
+
+const ROUND: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5, 0x3956_c25b, 0x59f1_11f1, 0x923f_82a4, 0xab1c_5ed5,
+    0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3, 0x72be_5d74, 0x80de_b1fe, 0x9bdc_06a7, 0xc19b_f174,
+    0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc, 0x2de9_2c6f, 0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da,
+    0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7, 0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967,
+    0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc, 0x5338_0d13, 0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85,
+    0xa2bf_e8a1, 0xa81a_664b, 0xc24b_8b70, 0xc76c_51a3, 0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070,
+    0x19a4_c116, 0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5, 0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208, 0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7, 0xc671_78f2,
+];
+
+const INITIAL: [u32; 8] = [
+    0x6a09_e667, 0xbb67_ae85, 0x3c6e_f372, 0xa54f_f53a, 0x510e_527f, 0x9b05_688c, 0x1f83_d9ab, 0x5be0_cd19,
+];
+
+/// Incremental SHA-256 (FIPS 180-4), so that digests are stable across
+/// platforms and Rust versions, unlike `core::hash`.
+pub struct Sha256 {
+    state: [u32; 8],
+    pending: Vec<u8>,
+    len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self {
+            state: INITIAL,
+            pending: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+}
+
+#[allow(clippy::indexing_slicing, clippy::many_single_char_names)]
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut words = [0_u32; 64];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for idx in 16..64 {
+        let (prev15, prev2) = (words[idx - 15], words[idx - 2]);
+        let sig0 = prev15.rotate_right(7) ^ prev15.rotate_right(18) ^ (prev15 >> 3_u32);
+        let sig1 = prev2.rotate_right(17) ^ prev2.rotate_right(19) ^ (prev2 >> 10_u32);
+        words[idx] = words[idx - 16]
+            .wrapping_add(sig0)
+            .wrapping_add(words[idx - 7])
+            .wrapping_add(sig1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (round, word) in ROUND.iter().zip(words) {
+        let sum1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let temp1 = h
+            .wrapping_add(sum1)
+            .wrapping_add(choice)
+            .wrapping_add(*round)
+            .wrapping_add(word);
+        let sum0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = sum0.wrapping_add(majority);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+    for (old, new) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *old = old.wrapping_add(new);
+    }
+}
+
+impl Sha256 {
+    pub fn update(&mut self, data: &[u8]) -> &mut Self {
+        self.len = self
+            .len
+            .wrapping_add(u64::try_from(data.len()).unwrap_or_default());
+        self.pending.extend_from_slice(data);
+        let full = self.pending.len() - self.pending.len() % 64;
+        for block in self.pending.get(..full).unwrap_or_default().chunks_exact(64) {
+            compress(&mut self.state, block);
+        }
+        self.pending.drain(..full);
+        self
+    }
+
+    /// Pads the pending bytes and returns the digest, consuming the hasher
+    /// since its state is no longer that of the data.
+    pub fn finish(mut self) -> [u8; 32] {
+        let bits = self.len.wrapping_mul(8);
+        let mut tail = vec![0x80_u8];
+        let padding = (119_usize - self.pending.len()) % 64;
+        tail.resize(padding.saturating_add(1), 0);
+        tail.extend_from_slice(&bits.to_be_bytes());
+        self.pending.extend_from_slice(&tail);
+        for block in self.pending.chunks_exact(64) {
+            compress(&mut self.state, block);
+        }
+        let mut res = [0_u8; 32];
+        for (bytes, word) in res.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        res
+    }
+}
+
+pub fn digest(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.update(data);
+    hasher.finish()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut acc, byte| {
+        let _ = write!(acc, "{byte:02x}");
+        acc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha(data: &[u8]) -> String {
+        hex(&digest(data))
+    }
+
+    #[test]
+    fn fips_vectors() {
+        assert_eq!(sha(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            sha(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha(&[b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    /// 55 bytes leave room for the length in the last block, 56 do not, 64
+    /// fill it.
+    #[test]
+    fn padding_boundaries() {
+        assert_eq!(sha(&[b'a'; 55]), "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318");
+        assert_eq!(sha(&[b'a'; 56]), "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a");
+        assert_eq!(sha(&[b'a'; 64]), "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb");
+    }
+
+    #[test]
+    fn split_updates() {
+        let data = [b'a'; 130];
+        for split in [0, 1, 55, 56, 63, 64, 65, 129] {
+            let (left, right) = data.split_at(split);
+            let mut hasher = Sha256::default();
+            hasher.update(left).update(right);
+            assert_eq!(hasher.finish(), digest(&data));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::errors::SResult;
+use crate::json::{self, ParsedValue, ParsingItem, Scalar};
+use crate::visit::{self, Path, PathElt, VisitorMut};
+use crate::{error, pointer};
+use core::cmp::Ordering;
+use core::ops::ControlFlow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Alphabetical,
+    /// Digit runs compare as numbers: `item2` < `item10`.
+    Natural,
+}
+
+impl Order {
+    pub fn from_name(name: &str) -> SResult<Self> {
+        match name {
+            "alpha" | "alphabetical" => Ok(Self::Alphabetical),
+            "natural" => Ok(Self::Natural),
+            _ => Err(error!("Unknown key order {name:?}, expected alphabetical or natural.")),
+        }
+    }
+
+    fn compare(self, left: &str, right: &str) -> Ordering {
+        match self {
+            Self::Alphabetical => left.cmp(right),
+            Self::Natural => natural(left, right),
+        }
+    }
+}
+
+fn natural(left: &str, right: &str) -> Ordering {
+    let mut left_chars = left.chars().peekable();
+    let mut right_chars = right.chars().peekable();
+    loop {
+        match (left_chars.peek().copied(), right_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(lch), Some(rch)) if lch.is_ascii_digit() && rch.is_ascii_digit() => {
+                let mut take = |chars: &mut core::iter::Peekable<core::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(ch);
+                    }
+                    digits
+                };
+                let (lnum, rnum) = (take(&mut left_chars), take(&mut right_chars));
+                let (ltrim, rtrim) = (lnum.trim_start_matches('0'), rnum.trim_start_matches('0'));
+                let order = ltrim
+                    .len()
+                    .cmp(&rtrim.len())
+                    .then_with(|| ltrim.cmp(rtrim))
+                    .then_with(|| lnum.len().cmp(&rnum.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(lch), Some(rch)) => {
+                if lch != rch {
+                    return lch.cmp(&rch);
+                }
+                left_chars.next();
+                right_chars.next();
+            }
+        }
+    }
+}
+
+/// Where and how object keys are reordered.
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub order: Option<Order>,
+    /// Preferred keys by JSON Pointer, `*` matching any one token. Listed keys
+    /// come first in the listed order, the others follow.
+    pub custom: Vec<(Vec<String>, Vec<String>)>,
+    /// Objects to sort, every object when empty.
+    pub paths: Vec<Vec<String>>,
+}
+
+impl SortOptions {
+    pub fn is_empty(&self) -> bool {
+        self.order.is_none() && self.custom.is_empty()
+    }
+
+    /// Reads a custom order file: an object mapping JSON Pointers to arrays
+    /// of keys.
+    pub fn load_custom(&mut self, value: &ParsedValue) -> SResult<()> {
+        let ParsedValue::Object(obj) = value else {
+            return Err(error!("A key order file must map JSON Pointers to arrays of keys."));
+        };
+        for item in obj {
+            let path = json::unescape(&item.key);
+            let ParsedValue::Array(keys) = &item.value else {
+                return Err(error!("The key order of {path:?} must be an array of keys."));
+            };
+            let keys = keys
+                .iter()
+                .map(|key| match key.scalar() {
+                    Some(Scalar::Str(key)) => Ok(key),
+                    _ => Err(error!("The key order of {path:?} must only hold strings.")),
+                })
+                .collect::<SResult<Vec<_>>>()?;
+            self.custom.push((pointer::parse(&path)?, keys));
+        }
+        Ok(())
+    }
+}
+
+fn matches(pattern: &[String], path: &Path) -> bool {
+    pattern.len() == path.0.len()
+        && pattern.iter().zip(&path.0).all(|(pat, elt)| match elt {
+            _ if pat == "*" => true,
+            PathElt::Key(key) => pat == key,
+            PathElt::Index(idx) => *pat == idx.to_string(),
+        })
+}
+
+struct Sorter<'opts> {
+    opts: &'opts SortOptions,
+}
+
+impl Sorter<'_> {
+    fn sort(&self, path: &Path, obj: &mut [ParsingItem]) {
+        let preferred = self
+            .opts
+            .custom
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, path))
+            .map(|(_, keys)| keys)
+            .next_back();
+        let in_scope = self.opts.paths.is_empty() || self.opts.paths.iter().any(|pattern| matches(pattern, path));
+        let order = self.opts.order.filter(|_| in_scope);
+        if preferred.is_none() && order.is_none() {
+            return;
+        }
+        let rank = |key: &str| {
+            preferred
+                .and_then(|keys| keys.iter().position(|pref| pref == key))
+                .unwrap_or(usize::MAX)
+        };
+        obj.sort_by(|left, right| {
+            let (lkey, rkey) = (json::unescape(&left.key), json::unescape(&right.key));
+            rank(&lkey)
+                .cmp(&rank(&rkey))
+                .then_with(|| order.map_or(Ordering::Equal, |order| order.compare(&lkey, &rkey)))
+        });
+    }
+}
+
+impl VisitorMut for Sorter<'_> {
+    type Break = ();
+
+    fn enter_mut(&mut self, path: &Path, value: &mut ParsedValue) -> ControlFlow<()> {
+        if let ParsedValue::Object(obj) = value {
+            self.sort(path, obj);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Reorders the keys of the objects selected by `opts`, keeping the original
+/// order between keys that compare equal.
+pub fn sort_keys(value: &mut ParsedValue, opts: &SortOptions) {
+    let _ = visit::visit_mut(&mut Sorter { opts }, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    fn sorted(text: &str, opts: &SortOptions) -> String {
+        let mut value = parse(text);
+        sort_keys(&mut value, opts);
+        json::stringify(&value).unwrap()
+    }
+
+    fn check(text: &str, opts: &SortOptions, expected: &str) {
+        assert_eq!(sorted(text, opts), json::stringify(&parse(expected)).unwrap());
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut keys = vec!["item10", "item2", "item1", "Item3", "item", "item02", "item002", "a1b10", "a1b9", "10", "9", "x0"];
+        keys.sort_by(|left, right| natural(left, right));
+        assert_eq!(keys, ["9", "10", "Item3", "a1b9", "a1b10", "item", "item1", "item2", "item02", "item002", "item10", "x0"]);
+    }
+
+    #[test]
+    fn leading_zeros_break_ties() {
+        assert_eq!(natural("a01", "a1"), Ordering::Greater);
+        assert_eq!(natural("a1", "a01"), Ordering::Less);
+        assert_eq!(natural("a01b", "a1c"), Ordering::Greater);
+        assert_eq!(natural("a007", "a007"), Ordering::Equal);
+        assert_eq!(natural("0", "00"), Ordering::Less);
+        assert_eq!(natural("a99999999999999999999999", "a100000000000000000000000"), Ordering::Less);
+    }
+
+    #[test]
+    fn orders() {
+        let text = r#"{"b10": {"z": 1, "a": 2}, "b9": [{"y": 1, "x": 2}], "A": 0}"#;
+        let alpha = SortOptions { order: Some(Order::Alphabetical), ..SortOptions::default() };
+        check(text, &alpha, r#"{"A": 0, "b10": {"a": 2, "z": 1}, "b9": [{"x": 2, "y": 1}]}"#);
+        let natural = SortOptions { order: Some(Order::Natural), ..SortOptions::default() };
+        check(text, &natural, r#"{"A": 0, "b9": [{"x": 2, "y": 1}], "b10": {"a": 2, "z": 1}}"#);
+        let scoped = SortOptions { paths: vec![vec!["b10".to_owned()]], ..alpha };
+        check(text, &scoped, r#"{"b10": {"a": 2, "z": 1}, "b9": [{"y": 1, "x": 2}], "A": 0}"#);
+        assert_eq!(Order::from_name("alpha"), Ok(Order::Alphabetical));
+        assert!(Order::from_name("reverse").is_err());
+    }
+
+    #[test]
+    fn custom_orders() {
+        let mut opts = SortOptions::default();
+        opts.load_custom(&parse(r#"{"": ["name", "version"], "/deps/*": ["b"]}"#)).unwrap();
+        assert_eq!(opts.custom.len(), 2);
+        let text = r#"{"deps": {"x": {"a": 1, "b": 2}}, "version": 1, "name": "n"}"#;
+        check(text, &opts, r#"{"name": "n", "version": 1, "deps": {"x": {"b": 2, "a": 1}}}"#);
+        let with_order = SortOptions { order: Some(Order::Alphabetical), ..opts };
+        check(r#"{"z": 1, "version": 2, "a": 3, "name": 4}"#, &with_order, r#"{"name": 4, "version": 2, "a": 3, "z": 1}"#);
+    }
+
+    #[test]
+    fn invalid_custom_orders() {
+        let mut opts = SortOptions::default();
+        assert!(opts.load_custom(&parse(r#"["a"]"#)).is_err());
+        assert!(opts.load_custom(&parse(r#"{"": "a"}"#)).is_err());
+        assert!(opts.load_custom(&parse(r#"{"": [1]}"#)).is_err());
+        assert!(opts.load_custom(&parse(r#"{"a": ["b"]}"#)).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::errors::SResult;
+use crate::json::{self, ParsedValue};
+use crate::ser::{FormatOptions, LineEnding};
+use crate::{error, pointer};
+
+fn unsupported(path: &str, why: &str) -> String {
+    let at = if path.is_empty() { "(root)" } else { path };
+    error!("Cannot write TOML at {at}: {why}.")
+}
+
+fn key(raw: &str) -> String {
+    let val = json::unescape(raw);
+    if !val.is_empty()
+        && val
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        val
+    } else {
+        format!("\"{}\"", json::escape(&val))
+    }
+}
+
+fn scalar(raw: &str, path: &str) -> SResult<String> {
+    if let Some(inner) = raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')) {
+        // Re-escaped, as TOML has no `\/`.
+        return Ok(format!("\"{}\"", json::escape(&json::unescape(inner))));
+    }
+    match raw {
+        "null" => Err(unsupported(path, "null has no TOML equivalent, remove the key instead")),
+        "true" | "false" => Ok(raw.to_owned()),
+        _ if raw.contains(['.', 'e', 'E']) => Ok(raw.to_owned()),
+        _ if raw.parse::<i64>().is_ok() => Ok(raw.to_owned()),
+        _ => Err(unsupported(path, &format!("{raw} does not fit in a 64-bit TOML integer"))),
+    }
+}
+
+/// Value on one line: inline arrays and inline tables.
+fn inline(value: &ParsedValue, path: &str) -> SResult<String> {
+    match value {
+        ParsedValue::Value(raw) => scalar(raw, path),
+        ParsedValue::Array(arr) => Ok(format!(
+            "[{}]",
+            arr.iter()
+                .enumerate()
+                .map(|(idx, elt)| inline(elt, &pointer::push(path, &idx.to_string())))
+                .collect::<SResult<Vec<_>>>()?
+                .join(", ")
+        )),
+        ParsedValue::Object(obj) if obj.is_empty() => Ok("{}".to_owned()),
+        ParsedValue::Object(obj) => Ok(format!(
+            "{{ {} }}",
+            obj.iter()
+                .map(|item| {
+                    let path = pointer::push(path, &json::unescape(&item.key));
+                    Ok(format!("{} = {}", key(&item.key), inline(&item.value, &path)?))
+                })
+                .collect::<SResult<Vec<_>>>()?
+                .join(", ")
+        )),
+    }
+}
+
+fn array_of_tables(value: &ParsedValue) -> Option<&[ParsedValue]> {
+    match value {
+        ParsedValue::Array(arr) if !arr.is_empty() && arr.iter().all(|elt| matches!(elt, ParsedValue::Object(_))) => {
+            Some(arr)
+        }
+        ParsedValue::Value(_) | ParsedValue::Array(_) | ParsedValue::Object(_) => None,
+    }
+}
+
+/// Body of a table: its key/value pairs, then its tables and arrays of tables.
+fn table(obj: &json::Parsed, header: &str, path: &str, res: &mut Vec<String>) -> SResult<()> {
+    let nested = |item: &json::ParsingItem| {
+        matches!(item.value, ParsedValue::Object(_)) || array_of_tables(&item.value).is_some()
+    };
+    for item in obj.iter().filter(|item| !nested(item)) {
+        let path = pointer::push(path, &json::unescape(&item.key));
+        res.push(format!("{} = {}", key(&item.key), inline(&item.value, &path)?));
+    }
+    for item in obj.iter().filter(|item| nested(item)) {
+        let header = if header.is_empty() { key(&item.key) } else { format!("{header}.{}", key(&item.key)) };
+        let path = pointer::push(path, &json::unescape(&item.key));
+        match &item.value {
+            ParsedValue::Object(sub) => {
+                res.push(String::new());
+                res.push(format!("[{header}]"));
+                table(sub, &header, &path, res)?;
+            }
+            value => {
+                for (idx, elt) in array_of_tables(value).unwrap_or_default().iter().enumerate() {
+                    if let ParsedValue::Object(sub) = elt {
+                        res.push(String::new());
+                        res.push(format!("[[{header}]]"));
+                        table(sub, &header, &pointer::push(&path, &idx.to_string()), res)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// TOML document of an object, which fails on null and on values outside of
+/// the TOML number ranges.
+pub fn to_string(value: &ParsedValue, opts: &FormatOptions) -> SResult<String> {
+    let ParsedValue::Object(obj) = value else {
+        return Err(unsupported(
+            "",
+            &format!("a TOML document is a table, found {}", value.type_name()),
+        ));
+    };
+    let mut lines = vec![];
+    table(obj, "", "", &mut lines)?;
+    if lines.first().is_some_and(String::is_empty) {
+        lines.remove(0);
+    }
+    let newline = match opts.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+    };
+    let mut res = lines.join(newline);
+    if opts.final_newline {
+        res.push_str(newline);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn toml(text: &str) -> SResult<String> {
+        to_string(&json::parse(&mut string2parsechar("", text)).unwrap(), &FormatOptions::default())
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(
+            toml(r#"{"title": "x", "db": {"port": 5432, "hosts": ["a", "b"], "opts": {}}, "n": 1.5, "ok": true, "servers": [{"name": "s1"}, {"name": "s2", "tags": {"a b": 1}}]}"#).unwrap(),
+            "title = \"x\"\nn = 1.5\nok = true\n\n[db]\nport = 5432\nhosts = [\"a\", \"b\"]\n\n[db.opts]\n\n[[servers]]\nname = \"s1\"\n\n[[servers]]\nname = \"s2\"\n\n[servers.tags]\n\"a b\" = 1"
+        );
+        assert_eq!(toml(r#"{"a": [[1], [{"b": 2}, {}]]}"#).unwrap(), "a = [[1], [{ b = 2 }, {}]]");
+        assert_eq!(toml(r#"{"a\"b": "c\/d"}"#).unwrap(), "\"a\\\"b\" = \"c/d\"");
+    }
+
+    #[test]
+    fn nulls_are_rejected() {
+        let err = toml(r#"{"a": {"b": [1, null]}}"#).unwrap_err();
+        assert!(err.contains("Cannot write TOML at /a/b/1: null has no TOML equivalent"), "{err}");
+        assert!(toml(r#"{"a": null}"#).unwrap_err().contains("at /a:"));
+    }
+
+    #[test]
+    fn roots_must_be_tables() {
+        for (text, found) in [("[1]", "array"), ("[{\"a\": 1}]", "array"), ("1", "number"), ("null", "null")] {
+            let err = toml(text).unwrap_err();
+            assert!(err.contains(&format!("at (root): a TOML document is a table, found {found}")), "{err}");
+        }
+    }
+
+    #[test]
+    fn integers_must_fit() {
+        assert_eq!(toml(r#"{"a": -9223372036854775808}"#).unwrap(), "a = -9223372036854775808");
+        assert!(toml(r#"{"a": 9223372036854775808}"#).unwrap_err().contains("does not fit in a 64-bit TOML integer"));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::convert::member;
+use crate::errors::SResult;
+use crate::json::{ParsedValue, Scalar};
+use crate::regex::Regex;
+use crate::{error, json, pointer, ParseCharElt};
+use std::collections::HashMap;
+
+/// Schemas applied in turn to the same value, through `$ref` and the in-place
+/// applicators, after which a schema is deemed cyclic. Descending into items
+/// and properties starts over, as the document itself is finite.
+const MAX_DEPTH: usize = 64;
+
+/// A keyword the instance does not satisfy, at a JSON Pointer of the instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub path: String,
+    pub msg: String,
+}
+
+/// Validator for JSON Schema draft 2020-12. Errors are for invalid schemas,
+/// failures for invalid instances.
+struct Validator<'schema> {
+    root: &'schema ParsedValue,
+}
+
+fn number(value: &ParsedValue) -> Option<f64> {
+    match value.scalar() {
+        Some(Scalar::Number(num)) => Some(num),
+        _ => None,
+    }
+}
+
+fn string(value: &ParsedValue) -> Option<String> {
+    match value.scalar() {
+        Some(Scalar::Str(val)) => Some(val),
+        _ => None,
+    }
+}
+
+fn fail(res: &mut Vec<Failure>, path: &str, msg: String) {
+    res.push(Failure {
+        path: path.to_owned(),
+        msg,
+    });
+}
+
+fn has_type(value: &ParsedValue, name: &str) -> bool {
+    match name {
+        "integer" => number(value).is_some_and(|num| num.fract() == 0.0_f64),
+        _ => value.type_name() == name,
+    }
+}
+
+fn count(value: &ParsedValue, keyword: &str) -> SResult<usize> {
+    number(value)
+        .filter(|num| *num >= 0.0_f64 && num.fract() == 0.0_f64)
+        .map(|num| format!("{num:.0}").parse().unwrap_or(usize::MAX))
+        .ok_or_else(|| error!("{keyword} must be a non-negative integer."))
+}
+
+fn schemas<'schema>(value: &'schema ParsedValue, keyword: &str) -> SResult<&'schema [ParsedValue]> {
+    match value {
+        ParsedValue::Array(arr) if !arr.is_empty() => Ok(arr),
+        _ => Err(error!("{keyword} must be a non-empty array of schemas.")),
+    }
+}
+
+impl<'schema> Validator<'schema> {
+    fn resolve(&self, reference: &str) -> SResult<&'schema ParsedValue> {
+        let fragment = reference
+            .strip_prefix('#')
+            .ok_or_else(|| error!("Only local references are supported, found {reference:?}."))?;
+        pointer::get(self.root, &pointer::parse(fragment)?)
+            .ok_or_else(|| error!("Reference {reference:?} does not resolve."))
+    }
+
+    fn is_valid(&self, schema: &ParsedValue, value: &ParsedValue, path: &str, depth: usize) -> SResult<bool> {
+        Ok(self.failures(schema, value, path, depth)?.is_empty())
+    }
+
+    fn failures(&self, schema: &ParsedValue, value: &ParsedValue, path: &str, depth: usize) -> SResult<Vec<Failure>> {
+        let depth = depth.saturating_add(1);
+        if depth > MAX_DEPTH {
+            return Err(error!("Schema recursion is too deep at {path:?}, is a $ref cyclic?"));
+        }
+        let mut res = vec![];
+        let keywords = match (schema, schema.scalar()) {
+            (ParsedValue::Object(keywords), _) => keywords,
+            (_, Some(Scalar::Bool(true))) => return Ok(vec![]),
+            (_, Some(Scalar::Bool(false))) => {
+                fail(&mut res, path, "no value is allowed here".to_owned());
+                return Ok(res);
+            }
+            _ => return Err(error!("A schema must be an object or a boolean.")),
+        };
+        let keyword = |name: &str| member(keywords, name);
+
+        if let Some(reference) = keyword("$ref") {
+            let target = string(reference).ok_or_else(|| error!("$ref must be a string."))?;
+            res.extend(self.failures(self.resolve(&target)?, value, path, depth)?);
+        }
+        if let Some(types) = keyword("type") {
+            let names = match types {
+                ParsedValue::Array(arr) => arr.iter().filter_map(string).collect(),
+                _ => string(types).into_iter().collect::<Vec<_>>(),
+            };
+            if !names.iter().any(|name| has_type(value, name)) {
+                fail(&mut res, path, format!("expected {}, found {}", names.join(" or "), value.type_name()));
+            }
+        }
+        if let Some(ParsedValue::Array(values)) = keyword("enum") {
+            if !values.iter().any(|val| val.same(value)) {
+                fail(&mut res, path, "value is not one of the enum values".to_owned());
+            }
+        }
+        if let Some(expected) = keyword("const") {
+            if !expected.same(value) {
+                fail(&mut res, path, format!("expected the constant {}", json::stringify(expected)?));
+            }
+        }
+
+        if let Some(num) = number(value) {
+            let bound = |name: &str| keyword(name).and_then(number);
+            if let Some(min) = bound("minimum").filter(|min| num < *min) {
+                fail(&mut res, path, format!("{} is less than the minimum {}", json::format_number(num), json::format_number(min)));
+            }
+            if let Some(max) = bound("maximum").filter(|max| num > *max) {
+                fail(&mut res, path, format!("{} is greater than the maximum {}", json::format_number(num), json::format_number(max)));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| num <= *min) {
+                fail(&mut res, path, format!("{} is not greater than {}", json::format_number(num), json::format_number(min)));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| num >= *max) {
+                fail(&mut res, path, format!("{} is not less than {}", json::format_number(num), json::format_number(max)));
+            }
+            if let Some(div) = bound("multipleOf") {
+                let quotient = num / div;
+                if (quotient - quotient.round()).abs() > 1e-9_f64 {
+                    fail(&mut res, path, format!("{} is not a multiple of {}", json::format_number(num), json::format_number(div)));
+                }
+            }
+        }
+
+        if let Some(text) = string(value) {
+            let len = text.chars().count();
+            if let Some(min) = keyword("minLength").map(|val| count(val, "minLength")).transpose()? {
+                if len < min {
+                    fail(&mut res, path, format!("string is shorter than {min} characters"));
+                }
+            }
+            if let Some(max) = keyword("maxLength").map(|val| count(val, "maxLength")).transpose()? {
+                if len > max {
+                    fail(&mut res, path, format!("string is longer than {max} characters"));
+                }
+            }
+            if let Some(pattern) = keyword("pattern").and_then(string) {
+                let regex = Regex::new(&pattern).map_err(|err| error!("Invalid pattern {pattern:?}: {err}."))?;
+                if !regex.is_match(&text) {
+                    fail(&mut res, path, format!("string does not match the pattern {pattern:?}"));
+                }
+            }
+        }
+
+        if let ParsedValue::Array(arr) = value {
+            if let Some(min) = keyword("minItems").map(|val| count(val, "minItems")).transpose()? {
+                if arr.len() < min {
+                    fail(&mut res, path, format!("array has fewer than {min} items"));
+                }
+            }
+            if let Some(max) = keyword("maxItems").map(|val| count(val, "maxItems")).transpose()? {
+                if arr.len() > max {
+                    fail(&mut res, path, format!("array has more than {max} items"));
+                }
+            }
+            if keyword("uniqueItems").and_then(ParsedValue::scalar) == Some(Scalar::Bool(true)) {
+                let duplicate = arr
+                    .iter()
+                    .enumerate()
+                    .any(|(idx, elt)| arr.iter().skip(idx.saturating_add(1)).any(|other| other.same(elt)));
+                if duplicate {
+                    fail(&mut res, path, "array items are not unique".to_owned());
+                }
+            }
+            let prefix = match keyword("prefixItems") {
+                Some(prefix) => schemas(prefix, "prefixItems")?,
+                None => &[],
+            };
+            for (idx, elt) in arr.iter().enumerate() {
+                let elt_path = pointer::push(path, &idx.to_string());
+                if let Some(sub) = prefix.get(idx).or_else(|| keyword("items")) {
+                    res.extend(self.failures(sub, elt, &elt_path, 0)?);
+                }
+            }
+        }
+
+        if let ParsedValue::Object(obj) = value {
+            if let Some(min) = keyword("minProperties").map(|val| count(val, "minProperties")).transpose()? {
+                if obj.len() < min {
+                    fail(&mut res, path, format!("object has fewer than {min} properties"));
+                }
+            }
+            if let Some(max) = keyword("maxProperties").map(|val| count(val, "maxProperties")).transpose()? {
+                if obj.len() > max {
+                    fail(&mut res, path, format!("object has more than {max} properties"));
+                }
+            }
+            if let Some(ParsedValue::Array(required)) = keyword("required") {
+                for name in required.iter().filter_map(string) {
+                    if member(obj, &name).is_none() {
+                        fail(&mut res, path, format!("missing required property {name:?}"));
+                    }
+                }
+            }
+            let no_props = vec![];
+            let props = match keyword("properties") {
+                Some(ParsedValue::Object(props)) => props,
+                _ => &no_props,
+            };
+            let patterns = match keyword("patternProperties") {
+                Some(ParsedValue::Object(patterns)) => patterns
+                    .iter()
+                    .map(|item| {
+                        let pattern = json::unescape(&item.key);
+                        Regex::new(&pattern)
+                            .map(|regex| (regex, &item.value))
+                            .map_err(|err| error!("Invalid pattern {pattern:?}: {err}."))
+                    })
+                    .collect::<SResult<Vec<_>>>()?,
+                _ => vec![],
+            };
+            for item in obj {
+                let key = json::unescape(&item.key);
+                let item_path = pointer::push(path, &key);
+                let mut matched = false;
+                if let Some(sub) = member(props, &key) {
+                    matched = true;
+                    res.extend(self.failures(sub, &item.value, &item_path, 0)?);
+                }
+                for (regex, sub) in &patterns {
+                    if regex.is_match(&key) {
+                        matched = true;
+                        res.extend(self.failures(sub, &item.value, &item_path, 0)?);
+                    }
+                }
+                match keyword("additionalProperties") {
+                    Some(sub) if !matched => match sub.scalar() {
+                        Some(Scalar::Bool(false)) => fail(&mut res, &item_path, format!("property {key:?} is not allowed")),
+                        _ => res.extend(self.failures(sub, &item.value, &item_path, 0)?),
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        if let Some(all) = keyword("allOf") {
+            for sub in schemas(all, "allOf")? {
+                res.extend(self.failures(sub, value, path, depth)?);
+            }
+        }
+        if let Some(any) = keyword("anyOf") {
+            let mut valid = false;
+            for sub in schemas(any, "anyOf")? {
+                if self.is_valid(sub, value, path, depth)? {
+                    valid = true;
+                    break;
+                }
+            }
+            if !valid {
+                fail(&mut res, path, "value does not match any schema of anyOf".to_owned());
+            }
+        }
+        if let Some(one) = keyword("oneOf") {
+            let mut matching = 0_usize;
+            for sub in schemas(one, "oneOf")? {
+                if self.is_valid(sub, value, path, depth)? {
+                    matching = matching.saturating_add(1);
+                }
+            }
+            if matching != 1 {
+                fail(&mut res, path, format!("value matches {matching} schemas of oneOf instead of exactly one"));
+            }
+        }
+        if let Some(not) = keyword("not") {
+            if self.is_valid(not, value, path, depth)? {
+                fail(&mut res, path, "value must not match the schema of not".to_owned());
+            }
+        }
+        if let Some(cond) = keyword("if") {
+            let branch = if self.is_valid(cond, value, path, depth)? {
+                keyword("then")
+            } else {
+                keyword("else")
+            };
+            if let Some(sub) = branch {
+                res.extend(self.failures(sub, value, path, depth)?);
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Every failure of `value` against `schema`, in document order of the
+/// keywords that found them.
+pub fn validate(schema: &ParsedValue, value: &ParsedValue) -> SResult<Vec<Failure>> {
+    Validator { root: schema }.failures(schema, value, "", 0)
+}
+
+/// Returns the failures, each raised at its position in the document when
+/// `positions` has it, and whether the document is valid.
+pub fn run(
+    value: &ParsedValue,
+    positions: &HashMap<String, ParseCharElt>,
+    schema: &ParsedValue,
+) -> SResult<(String, bool)> {
+    let failures = validate(schema, value)?;
+    if failures.is_empty() {
+        return Ok(("The document is valid.".to_owned(), true));
+    }
+    let lines = failures
+        .iter()
+        .map(|failure| {
+            let shown = if failure.path.is_empty() { "(root)" } else { &failure.path };
+            let msg = format!("at {shown}: {}", failure.msg);
+            positions
+                .get(&failure.path)
+                .map_or_else(|| error!("{msg}"), |elt| crate::raise(&msg, elt))
+        })
+        .collect::<Vec<_>>();
+    Ok((lines.join("\n"), false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    /// Pointers of the failures, with their messages.
+    fn failures(schema: &str, value: &str) -> Vec<(String, String)> {
+        validate(&parse(schema), &parse(value))
+            .unwrap()
+            .into_iter()
+            .map(|failure| (failure.path, failure.msg))
+            .collect()
+    }
+
+    fn valid(schema: &str, value: &str) -> bool {
+        failures(schema, value).is_empty()
+    }
+
+    #[test]
+    fn boolean_schemas() {
+        assert!(valid("true", "1"));
+        assert!(!valid("false", "1"));
+    }
+
+    #[test]
+    fn type_enum_const() {
+        assert!(valid(r#"{"type": "integer"}"#, "3.0"));
+        assert!(!valid(r#"{"type": "integer"}"#, "3.5"));
+        assert!(valid(r#"{"type": ["string", "null"]}"#, "null"));
+        assert_eq!(
+            failures(r#"{"type": ["string", "null"]}"#, "1"),
+            [(String::new(), "expected string or null, found number".to_owned())]
+        );
+        assert!(valid(r#"{"enum": [1, "a"]}"#, "1.0"));
+        assert!(!valid(r#"{"enum": [1, "a"]}"#, r#""b""#));
+        assert!(valid(r#"{"const": {"a": [1]}}"#, r#"{"a": [1]}"#));
+        assert!(!valid(r#"{"const": {"a": [1]}}"#, r#"{"a": [2]}"#));
+    }
+
+    #[test]
+    fn numbers() {
+        let schema = r#"{"minimum": 1, "exclusiveMaximum": 10, "multipleOf": 0.5}"#;
+        assert!(valid(schema, "1"));
+        assert!(valid(schema, "9.5"));
+        assert!(!valid(schema, "0.5"));
+        assert!(!valid(schema, "10"));
+        assert!(!valid(schema, "2.25"));
+    }
+
+    #[test]
+    fn strings() {
+        let schema = r#"{"minLength": 2, "maxLength": 3, "pattern": "^[a-z]+$"}"#;
+        assert!(valid(schema, r#""ab""#));
+        assert!(!valid(schema, r#""a""#));
+        assert!(!valid(schema, r#""abcd""#));
+        assert!(!valid(schema, r#""aB""#));
+        // Lengths count characters rather than bytes.
+        assert!(valid(r#"{"maxLength": 2}"#, r#""éé""#));
+    }
+
+    #[test]
+    fn arrays() {
+        let schema = r#"{"prefixItems": [{"type": "string"}], "items": {"type": "number"}, "minItems": 1, "uniqueItems": true}"#;
+        assert!(valid(schema, r#"["a", 1, 2]"#));
+        assert!(!valid(schema, "[]"));
+        assert_eq!(failures(schema, r#"["a", 1, "b"]"#), [("/2".to_owned(), "expected number, found string".to_owned())]);
+        assert!(!valid(schema, r#"["a", 1, 1.0]"#));
+    }
+
+    #[test]
+    fn objects() {
+        let schema = r#"{
+            "required": ["id"],
+            "properties": {"id": {"type": "integer"}},
+            "patternProperties": {"^x-": {"type": "string"}},
+            "additionalProperties": false
+        }"#;
+        assert!(valid(schema, r#"{"id": 1, "x-note": "a"}"#));
+        assert_eq!(
+            failures(schema, r#"{"x-note": 1, "other": true}"#),
+            [
+                (String::new(), "missing required property \"id\"".to_owned()),
+                ("/x-note".to_owned(), "expected string, found number".to_owned()),
+                ("/other".to_owned(), "property \"other\" is not allowed".to_owned()),
+            ]
+        );
+        assert!(!valid(r#"{"maxProperties": 1}"#, r#"{"a": 1, "b": 2}"#));
+    }
+
+    #[test]
+    fn any_of() {
+        let schema = r#"{"anyOf": [{"type": "string"}, {"minimum": 5}]}"#;
+        assert!(valid(schema, r#""a""#));
+        assert!(valid(schema, "7"));
+        assert!(!valid(schema, "3"));
+    }
+
+    #[test]
+    fn one_of() {
+        let schema = r#"{"oneOf": [{"type": "number", "maximum": 5}, {"type": "number", "minimum": 3}]}"#;
+        assert!(valid(schema, "1"));
+        assert_eq!(
+            failures(schema, "4"),
+            [(String::new(), "value matches 2 schemas of oneOf instead of exactly one".to_owned())]
+        );
+        assert!(!valid(schema, r#""a""#));
+    }
+
+    #[test]
+    fn all_of_and_not() {
+        assert!(!valid(r#"{"allOf": [{"type": "number"}, {"maximum": 1}]}"#, "2"));
+        assert!(valid(r#"{"not": {"type": "string"}}"#, "1"));
+        assert!(!valid(r#"{"not": {"type": "string"}}"#, r#""a""#));
+    }
+
+    #[test]
+    fn if_then_else() {
+        let schema = r#"{
+            "if": {"properties": {"kind": {"const": "circle"}}},
+            "then": {"required": ["radius"]},
+            "else": {"required": ["side"]}
+        }"#;
+        assert!(valid(schema, r#"{"kind": "circle", "radius": 1}"#));
+        assert!(!valid(schema, r#"{"kind": "circle", "side": 1}"#));
+        assert!(valid(schema, r#"{"kind": "square", "side": 1}"#));
+        assert!(!valid(schema, r#"{"kind": "square"}"#));
+        // Without `else`, a failed condition accepts the value.
+        assert!(valid(r#"{"if": {"type": "string"}, "then": {"minLength": 2}}"#, "1"));
+    }
+
+    #[test]
+    fn references() {
+        let schema = r##"{"$defs": {"pos": {"minimum": 0}}, "items": {"$ref": "#/$defs/pos"}}"##;
+        assert!(valid(schema, "[0, 1]"));
+        assert_eq!(failures(schema, "[1, -1]")[0].0, "/1");
+        assert!(validate(&parse(r##"{"$ref": "#"}"##), &parse("1")).is_err());
+        assert!(validate(&parse(r##"{"anyOf": [{"$ref": "#"}]}"##), &parse("1")).is_err());
+        assert!(validate(&parse(r#"{"pattern": "("}"#), &parse(r#""a""#)).is_err());
+    }
+
+    #[test]
+    fn deep_documents() {
+        let schema = r##"{
+            "$defs": {"node": {"type": "object", "properties": {"c": {"$ref": "#/$defs/node"}}}},
+            "$ref": "#/$defs/node"
+        }"##;
+        let nested = format!("{}{{}}{}", r#"{"c": "#.repeat(100), "}".repeat(100));
+        assert!(valid(schema, &nested));
+        let invalid = format!("{}1{}", r#"{"c": "#.repeat(100), "}".repeat(100));
+        assert_eq!(failures(schema, &invalid)[0].0, "/c".repeat(100));
+
+        let schema = r##"{"$defs": {"list": {"type": "array", "items": {"$ref": "#/$defs/list"}}}, "$ref": "#/$defs/list"}"##;
+        assert!(valid(schema, &format!("{}{}", "[".repeat(100), "]".repeat(100))));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::errors::SResult;
+use crate::json::{self, ParsedValue};
+use crate::{error, ser};
+
+/// Limits of the tree view, 0 disabling them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeOptions {
+    /// Characters kept from long strings.
+    pub max_string: usize,
+    /// Children shown for each array or object.
+    pub max_items: usize,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self {
+            max_string: 60,
+            max_items: 20,
+        }
+    }
+}
+
+/// Cuts `text` to `width` characters, ending it with an ellipsis.
+fn truncate(text: &str, width: usize) -> String {
+    if width == 0 || text.chars().count() <= width {
+        return text.to_owned();
+    }
+    let mut res = text.chars().take(width.saturating_sub(1)).collect::<String>();
+    res.push('…');
+    res
+}
+
+/// Single-line text of a scalar, strings keeping their escapes.
+fn scalar_text(raw: &str) -> &str {
+    raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')).unwrap_or(raw)
+}
+
+fn label(value: &ParsedValue, opts: &TreeOptions) -> String {
+    match value {
+        ParsedValue::Array(arr) => format!("(array, {} items)", arr.len()),
+        ParsedValue::Object(obj) => format!("(object, {} keys)", obj.len()),
+        ParsedValue::Value(raw) if raw.starts_with('"') => {
+            format!("\"{}\" ({})", truncate(scalar_text(raw), opts.max_string), value.type_name())
+        }
+        ParsedValue::Value(raw) => format!("{raw} ({})", value.type_name()),
+    }
+}
+
+fn branch(value: &ParsedValue, prefix: &str, opts: &TreeOptions, res: &mut Vec<String>) {
+    let children = match value {
+        ParsedValue::Array(arr) => arr.iter().enumerate().map(|(idx, elt)| (format!("[{idx}]"), elt)).collect(),
+        ParsedValue::Object(obj) => obj.iter().map(|item| (item.key.clone(), &item.value)).collect(),
+        ParsedValue::Value(_) => vec![],
+    };
+    let shown = if opts.max_items == 0 { children.len() } else { children.len().min(opts.max_items) };
+    let hidden = children.len().saturating_sub(shown);
+    for (idx, (name, child)) in children.into_iter().take(shown).enumerate() {
+        let last = hidden == 0 && idx.saturating_add(1) == shown;
+        let (connector, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        res.push(format!("{prefix}{connector}{name}: {}", label(child, opts)));
+        branch(child, &format!("{prefix}{indent}"), opts, res);
+    }
+    if hidden > 0 {
+        res.push(format!("{prefix}└── … {hidden} more"));
+    }
+}
+
+/// Indented outline of a document, each value tagged with its type.
+pub fn tree(value: &ParsedValue, opts: &TreeOptions) -> String {
+    let mut res = vec![label(value, opts)];
+    branch(value, "", opts, &mut res);
+    res.join("\n")
+}
+
+fn cell(value: &ParsedValue) -> SResult<String> {
+    match value {
+        ParsedValue::Value(raw) => Ok(scalar_text(raw).to_owned()),
+        ParsedValue::Array(_) | ParsedValue::Object(_) => ser::to_string(
+            value,
+            &ser::FormatOptions {
+                minify: true,
+                ..ser::FormatOptions::default()
+            },
+        ),
+    }
+}
+
+/// Narrowest a column gets before the table overflows instead.
+const MIN_COLUMN: usize = 3;
+
+/// Aligned columns of an array of objects, one per key in order of first
+/// appearance, the widest being shrunk until the table fits in `width`.
+pub fn table(value: &ParsedValue, width: usize) -> SResult<String> {
+    let ParsedValue::Array(rows) = value else {
+        return Err(error!("A table needs an array of objects, found {}.", value.type_name()));
+    };
+    let mut keys: Vec<&str> = vec![];
+    for row in rows {
+        let ParsedValue::Object(obj) = row else {
+            return Err(error!("A table needs an array of objects, found an array holding {}.", row.type_name()));
+        };
+        for item in obj {
+            if !keys.contains(&item.key.as_str()) {
+                keys.push(&item.key);
+            }
+        }
+    }
+    let mut cells = vec![keys.iter().map(|key| json::unescape(key)).collect::<Vec<_>>()];
+    for row in rows {
+        if let ParsedValue::Object(obj) = row {
+            cells.push(
+                keys.iter()
+                    .map(|key| json::get(obj, key).map_or_else(|| Ok(String::new()), cell))
+                    .collect::<SResult<_>>()?,
+            );
+        }
+    }
+    let mut widths = keys
+        .iter()
+        .enumerate()
+        .map(|(col, _)| {
+            cells
+                .iter()
+                .filter_map(|line| line.get(col))
+                .map(|text| text.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let borders = keys.len().saturating_sub(1).saturating_mul(3);
+    while widths.iter().sum::<usize>().saturating_add(borders) > width {
+        match widths.iter_mut().filter(|col| **col > MIN_COLUMN).max_by_key(|col| **col) {
+            Some(widest) => *widest = widest.saturating_sub(1),
+            None => break,
+        }
+    }
+    let line = |texts: &[String]| {
+        texts
+            .iter()
+            .zip(&widths)
+            .map(|(text, col)| format!("{:<col$}", truncate(text, *col)))
+            .collect::<Vec<_>>()
+            .join(" │ ")
+            .trim_end()
+            .to_owned()
+    };
+    let mut res = vec![];
+    for (idx, texts) in cells.iter().enumerate() {
+        res.push(line(texts));
+        if idx == 0 {
+            res.push(widths.iter().map(|col| "─".repeat(*col)).collect::<Vec<_>>().join("─┼─"));
+        }
+    }
+    Ok(res.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    #[test]
+    fn trees() {
+        let value = parse(r#"{"a": [1, "long string here", null], "b": {"c": true}, "d": [1, 2, 3, 4]}"#);
+        assert_eq!(
+            tree(&value, &TreeOptions { max_string: 8, max_items: 3 }),
+            "(object, 3 keys)
+├── a: (array, 3 items)
+│   ├── [0]: 1 (number)
+│   ├── [1]: \"long st…\" (string)
+│   └── [2]: null (null)
+├── b: (object, 1 keys)
+│   └── c: true (boolean)
+└── d: (array, 4 items)
+    ├── [0]: 1 (number)
+    ├── [1]: 2 (number)
+    ├── [2]: 3 (number)
+    └── … 1 more"
+        );
+        let unlimited = tree(&value, &TreeOptions { max_string: 0, max_items: 0 });
+        assert!(unlimited.contains("\"long string here\""));
+        assert!(unlimited.ends_with("    └── [3]: 4 (number)"));
+        assert_eq!(tree(&parse("[]"), &TreeOptions::default()), "(array, 0 items)");
+    }
+
+    #[test]
+    fn tables() {
+        let value = parse(r#"[{"id": 1, "na\"me": "alpha"}, {"id": 22, "tags": [1, 2]}]"#);
+        assert_eq!(
+            table(&value, 80).unwrap(),
+            "id │ na\"me │ tags
+───┼───────┼──────
+1  │ alpha │
+22 │       │ [1,2]"
+        );
+    }
+
+    #[test]
+    fn narrow_tables() {
+        let value = parse(r#"[{"id": 1, "name": "a very long name indeed"}, {"id": 22, "name": "short"}]"#);
+        assert_eq!(
+            table(&value, 16).unwrap(),
+            "id │ name
+───┼────────────
+1  │ a very lon…
+22 │ short"
+        );
+        // Columns do not shrink below three characters.
+        assert!(table(&value, 1).unwrap().starts_with("id │ na…\n───┼────\n1  │ a …"));
+    }
+
+    #[test]
+    fn tables_need_arrays_of_objects() {
+        assert!(table(&parse(r#"{"a": 1}"#), 80).is_err());
+        assert!(table(&parse(r#"[{"a": 1}, 2]"#), 80).is_err());
+        assert_eq!(table(&parse("[]"), 80).unwrap(), "\n");
+    }
+}
@@ -0,0 +1,309 @@
+use crate::json::{Parsed, ParsedValue, ParsingItem};
+use crate::pointer;
+use core::fmt;
+use core::ops::ControlFlow;
+
+pub use crate::json::{Fold, Visitor, VisitorMut};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathElt {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of the value being visited, from the root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path(pub Vec<PathElt>);
+
+impl Path {
+    pub fn push(&mut self, elt: PathElt) {
+        self.0.push(elt);
+    }
+
+    pub fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    /// Renders the path as a JSON Pointer.
+    pub fn to_pointer(&self) -> String {
+        self.0.iter().fold(String::new(), |acc, elt| match elt {
+            PathElt::Key(key) => pointer::push(&acc, key),
+            PathElt::Index(idx) => pointer::push(&acc, &idx.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.to_pointer())
+    }
+}
+
+/// Default recursion into the children of a variant's content.
+pub trait Traverse: Sized {
+    fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break>;
+    fn walk_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break>;
+    fn fold<F: Fold + ?Sized>(self, folder: &mut F, path: &mut Path) -> ControlFlow<F::Break, Self>;
+}
+
+impl Traverse for String {
+    fn walk<V: Visitor + ?Sized>(&self, _: &mut V, _: &mut Path) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
+
+    fn walk_mut<V: VisitorMut + ?Sized>(&mut self, _: &mut V, _: &mut Path) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
+
+    fn fold<F: Fold + ?Sized>(self, _: &mut F, _: &mut Path) -> ControlFlow<F::Break, Self> {
+        ControlFlow::Continue(self)
+    }
+}
+
+impl Traverse for Vec<ParsedValue> {
+    fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break> {
+        for (idx, elt) in self.iter().enumerate() {
+            path.push(PathElt::Index(idx));
+            let flow = visitor.visit(path, elt);
+            path.pop();
+            flow?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn walk_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break> {
+        for (idx, elt) in self.iter_mut().enumerate() {
+            path.push(PathElt::Index(idx));
+            let flow = visitor.visit_mut(path, elt);
+            path.pop();
+            flow?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn fold<F: Fold + ?Sized>(self, folder: &mut F, path: &mut Path) -> ControlFlow<F::Break, Self> {
+        let mut res = Self::with_capacity(self.len());
+        for (idx, elt) in self.into_iter().enumerate() {
+            path.push(PathElt::Index(idx));
+            let flow = folder.fold(path, elt);
+            path.pop();
+            res.push(flow?);
+        }
+        ControlFlow::Continue(res)
+    }
+}
+
+impl Traverse for Parsed {
+    fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break> {
+        for item in self {
+            path.push(PathElt::Key(crate::json::unescape(&item.key)));
+            let flow = visitor.visit(path, &item.value);
+            path.pop();
+            flow?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn walk_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V, path: &mut Path) -> ControlFlow<V::Break> {
+        for item in self {
+            path.push(PathElt::Key(crate::json::unescape(&item.key)));
+            let flow = visitor.visit_mut(path, &mut item.value);
+            path.pop();
+            flow?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn fold<F: Fold + ?Sized>(self, folder: &mut F, path: &mut Path) -> ControlFlow<F::Break, Self> {
+        let mut res = Self::with_capacity(self.len());
+        for item in self {
+            path.push(PathElt::Key(crate::json::unescape(&item.key)));
+            let flow = folder.fold(path, item.value);
+            path.pop();
+            res.push(ParsingItem {
+                key: item.key,
+                value: flow?,
+            });
+        }
+        ControlFlow::Continue(res)
+    }
+}
+
+/// Generates the `Visitor`, `VisitorMut` and `Fold` traits, with one method
+/// per variant whose default recurses into the variant's content. It is
+/// invoked by `define_enum_and_ref`, next to the `to_ref` and `to_refmut` it
+/// matches with.
+macro_rules! define_traversal {
+    ($name:ident, $nameref:ident, $namerefmut:ident, $($variant:ident($t:ty) => $visit:ident, $visit_mut:ident, $fold:ident),*) => {
+        /// Read-only traversal: return `ControlFlow::Break` to stop early.
+        pub trait Visitor {
+            type Break;
+
+            /// Called on every value before recursing into it.
+            fn enter(&mut self, path: &$crate::visit::Path, value: &$name) -> ::core::ops::ControlFlow<Self::Break> {
+                ::core::ops::ControlFlow::Continue(())
+            }
+
+            fn visit(&mut self, path: &mut $crate::visit::Path, value: &$name) -> ::core::ops::ControlFlow<Self::Break> {
+                self.enter(path, value)?;
+                match to_ref(value) {
+                    $($nameref::$variant(content) => self.$visit(path, content),)*
+                }
+            }
+
+            $(fn $visit(&mut self, path: &mut $crate::visit::Path, content: &$t) -> ::core::ops::ControlFlow<Self::Break> {
+                $crate::visit::Traverse::walk(content, self, path)
+            })*
+        }
+
+        /// In-place traversal: return `ControlFlow::Break` to stop early.
+        pub trait VisitorMut {
+            type Break;
+
+            /// Called on every value before recursing into it.
+            fn enter_mut(&mut self, path: &$crate::visit::Path, value: &mut $name) -> ::core::ops::ControlFlow<Self::Break> {
+                ::core::ops::ControlFlow::Continue(())
+            }
+
+            fn visit_mut(&mut self, path: &mut $crate::visit::Path, value: &mut $name) -> ::core::ops::ControlFlow<Self::Break> {
+                self.enter_mut(path, value)?;
+                match to_refmut(value) {
+                    $($namerefmut::$variant(content) => self.$visit_mut(path, content),)*
+                }
+            }
+
+            $(fn $visit_mut(&mut self, path: &mut $crate::visit::Path, content: &mut $t) -> ::core::ops::ControlFlow<Self::Break> {
+                $crate::visit::Traverse::walk_mut(content, self, path)
+            })*
+        }
+
+        /// Owning traversal that rebuilds the tree, children first.
+        pub trait Fold {
+            type Break;
+
+            fn fold(&mut self, path: &mut $crate::visit::Path, value: $name) -> ::core::ops::ControlFlow<Self::Break, $name> {
+                match value {
+                    $($name::$variant(content) => self.$fold(path, content),)*
+                }
+            }
+
+            $(fn $fold(&mut self, path: &mut $crate::visit::Path, content: $t) -> ::core::ops::ControlFlow<Self::Break, $name> {
+                ::core::ops::ControlFlow::Continue($name::$variant($crate::visit::Traverse::fold(content, self, path)?))
+            })*
+        }
+    };
+}
+
+pub(crate) use define_traversal;
+
+/// Runs a visitor from the root.
+pub fn visit<V: Visitor + ?Sized>(visitor: &mut V, value: &ParsedValue) -> ControlFlow<V::Break> {
+    visitor.visit(&mut Path::default(), value)
+}
+
+pub fn visit_mut<V: VisitorMut + ?Sized>(visitor: &mut V, value: &mut ParsedValue) -> ControlFlow<V::Break> {
+    visitor.visit_mut(&mut Path::default(), value)
+}
+
+pub fn fold<F: Fold + ?Sized>(folder: &mut F, value: ParsedValue) -> ControlFlow<F::Break, ParsedValue> {
+    folder.fold(&mut Path::default(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use crate::string2parsechar;
+    use core::convert::Infallible;
+
+    fn parse(text: &str) -> ParsedValue {
+        json::parse(&mut string2parsechar("", text)).unwrap()
+    }
+
+    /// Pointers of every value, in visiting order.
+    struct Pointers(Vec<String>);
+
+    impl Visitor for Pointers {
+        type Break = Infallible;
+
+        fn enter(&mut self, path: &Path, _: &ParsedValue) -> ControlFlow<Infallible> {
+            self.0.push(path.to_pointer());
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn paths() {
+        let mut pointers = Pointers(Vec::new());
+        let _ = visit(&mut pointers, &parse(r#"{"a": [1, {"b/c": null}], "d~": {}}"#));
+        assert_eq!(pointers.0, ["", "/a", "/a/0", "/a/1", "/a/1/b~1c", "/d~0"]);
+    }
+
+    /// Stops at the first string.
+    struct FirstString;
+
+    impl Visitor for FirstString {
+        type Break = String;
+
+        fn visit_scalar(&mut self, path: &mut Path, raw: &String) -> ControlFlow<String> {
+            if raw.starts_with('"') {
+                return ControlFlow::Break(format!("{path} {raw}"));
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn early_exit() {
+        let value = parse(r#"[1, [true, "x", "y"], "z"]"#);
+        assert_eq!(visit(&mut FirstString, &value), ControlFlow::Break("/1/1 \"x\"".to_owned()));
+        assert_eq!(visit(&mut FirstString, &parse("[1, {}]")), ControlFlow::Continue(()));
+    }
+
+    /// Doubles every number.
+    struct Double;
+
+    impl VisitorMut for Double {
+        type Break = Infallible;
+
+        fn visit_scalar_mut(&mut self, _: &mut Path, raw: &mut String) -> ControlFlow<Infallible> {
+            if let Ok(num) = raw.parse::<f64>() {
+                *raw = json::format_number(num * 2.0);
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn in_place() {
+        let mut value = parse(r#"{"a": [1, {"b": 2.5}], "c": "3"}"#);
+        let _ = visit_mut(&mut Double, &mut value);
+        assert!(value.same(&parse(r#"{"a": [2, {"b": 5}], "c": "3"}"#)));
+    }
+
+    /// Replaces the arrays by their length, failing below `/stop`.
+    struct Lengths;
+
+    impl Fold for Lengths {
+        type Break = Path;
+
+        fn fold_array(&mut self, path: &mut Path, arr: Vec<ParsedValue>) -> ControlFlow<Path, ParsedValue> {
+            if path.0.first() == Some(&PathElt::Key("stop".to_owned())) {
+                return ControlFlow::Break(path.clone());
+            }
+            let len = arr.fold(self, path)?.len();
+            ControlFlow::Continue(ParsedValue::Value(len.to_string()))
+        }
+    }
+
+    #[test]
+    fn folds() {
+        let value = fold(&mut Lengths, parse(r#"{"a": [[1, 2], 3], "b": {"c": []}}"#));
+        assert_eq!(
+            json::stringify(&value.continue_value().unwrap()).unwrap(),
+            json::stringify(&parse(r#"{"a": 2, "b": {"c": 0}}"#)).unwrap()
+        );
+        let stopped = fold(&mut Lengths, parse(r#"{"a": [], "stop": {"b": [[]]}}"#));
+        assert_eq!(stopped.break_value().map(|path| path.to_pointer()).as_deref(), Some("/stop/b"));
+    }
+}
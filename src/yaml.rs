@@ -0,0 +1,140 @@
+use crate::json::{self, ParsedValue, Scalar};
+use crate::ser::{FormatOptions, Indent, LineEnding};
+
+/// Plain scalars that YAML would read as something other than a string.
+const RESERVED: &[&str] = &[
+    "~", "null", "Null", "NULL", "true", "True", "TRUE", "false", "False", "FALSE", "y", "Y", "yes", "Yes", "YES",
+    "n", "N", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF",
+];
+
+/// Whether a string can be written without quotes and read back as the same
+/// string.
+fn plain(val: &str) -> bool {
+    let Some(first) = val.chars().next() else {
+        return false;
+    };
+    !RESERVED.contains(&val)
+        && !"-?:,[]{}#&*!|>'\"%@`".contains(first)
+        && !first.is_whitespace()
+        // Dates, times and numbers in other bases.
+        && !first.is_ascii_digit()
+        && !val.ends_with(char::is_whitespace)
+        && !val.contains(": ")
+        && !val.contains(" #")
+        && !val.ends_with(':')
+        && !val.chars().any(char::is_control)
+        && !matches!(json::to_scalar(val), Scalar::Number(_))
+        && !val.starts_with(['.', '+'])
+}
+
+/// A string, plain when possible and otherwise double-quoted, the JSON
+/// escapes being valid YAML ones.
+fn string(raw_escaped: &str) -> String {
+    let val = json::unescape(raw_escaped);
+    if plain(&val) {
+        val
+    } else {
+        format!("\"{}\"", json::escape(&val))
+    }
+}
+
+fn scalar(raw: &str) -> String {
+    match raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')) {
+        Some(inner) => string(inner),
+        None => raw.to_owned(),
+    }
+}
+
+/// Lines of a value, without the indentation of its parent.
+fn block(value: &ParsedValue, unit: &str, res: &mut Vec<String>) {
+    match value {
+        ParsedValue::Value(raw) => res.push(scalar(raw)),
+        ParsedValue::Array(arr) if arr.is_empty() => res.push("[]".to_owned()),
+        ParsedValue::Object(obj) if obj.is_empty() => res.push("{}".to_owned()),
+        ParsedValue::Array(arr) => {
+            for elt in arr {
+                let mut lines = vec![];
+                block(elt, unit, &mut lines);
+                for (idx, line) in lines.into_iter().enumerate() {
+                    res.push(format!("{}{line}", if idx == 0 { "- " } else { "  " }));
+                }
+            }
+        }
+        ParsedValue::Object(obj) => {
+            for item in obj {
+                let key = string(&item.key);
+                match &item.value {
+                    ParsedValue::Array(arr) if !arr.is_empty() => (),
+                    ParsedValue::Object(obj) if !obj.is_empty() => (),
+                    ParsedValue::Value(_) | ParsedValue::Array(_) | ParsedValue::Object(_) => {
+                        let mut lines = vec![];
+                        block(&item.value, unit, &mut lines);
+                        res.push(format!("{key}: {}", lines.concat()));
+                        continue;
+                    }
+                }
+                res.push(format!("{key}:"));
+                let mut lines = vec![];
+                block(&item.value, unit, &mut lines);
+                res.extend(lines.into_iter().map(|line| format!("{unit}{line}")));
+            }
+        }
+    }
+}
+
+/// Block-style YAML, which represents every JSON document.
+pub fn to_string(value: &ParsedValue, opts: &FormatOptions) -> String {
+    let unit = match opts.indent {
+        Indent::Spaces(width) => " ".repeat(width.max(1)),
+        // Tabs cannot indent YAML.
+        Indent::Tabs => "  ".to_owned(),
+    };
+    let newline = match opts.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+    };
+    let mut lines = vec![];
+    block(value, &unit, &mut lines);
+    let mut res = lines.join(newline);
+    if opts.final_newline {
+        res.push_str(newline);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string2parsechar;
+
+    fn yaml(text: &str) -> String {
+        to_string(&json::parse(&mut string2parsechar("", text)).unwrap(), &FormatOptions::default())
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(
+            yaml(r#"{"a": [1, {"b": true, "c": [null]}], "d": {}, "e": [], "f": {"g": "h"}}"#),
+            "a:\n    - 1\n    - b: true\n      c:\n          - null\nd: {}\ne: []\nf:\n    g: h"
+        );
+        assert_eq!(yaml("[[1, 2], 3]"), "- - 1\n  - 2\n- 3");
+        let opts = FormatOptions { indent: Indent::Tabs, line_ending: LineEnding::CrLf, final_newline: true, ..FormatOptions::default() };
+        assert_eq!(to_string(&json::parse(&mut string2parsechar("", r#"{"a": {"b": 1}}"#)).unwrap(), &opts), "a:\r\n  b: 1\r\n");
+    }
+
+    #[test]
+    fn ambiguous_scalars_are_quoted() {
+        for val in ["", "null", "~", "true", "No", "on", "1", "1.5", "0x1F", "2024-01-01", ".inf", "+1", "-a", "a: b", "a #b", "a:", " a", "a ", "[a]", "*a", "a\tb", "'a'"] {
+            let text = json::stringify(&ParsedValue::string(val)).unwrap();
+            assert_eq!(yaml(&text), format!("\"{}\"", json::escape(val)), "{val:?}");
+        }
+        for val in ["a", "hello world", "a:b", "a#b", "it's", "é"] {
+            assert_eq!(yaml(&json::stringify(&ParsedValue::string(val)).unwrap()), val);
+        }
+    }
+
+    #[test]
+    fn keys_are_quoted_like_strings() {
+        assert_eq!(yaml(r#"{"yes": 1, "a b": 2, "1": 3, "a\"b": 4}"#), "\"yes\": 1\na b: 2\n\"1\": 3\na\"b: 4");
+    }
+}
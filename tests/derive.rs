@@ -0,0 +1,226 @@
+use parsing::convert::{FromJson, ToJson};
+use parsing::json::{self, ParsedValue};
+use parsing::string2parsechar;
+use std::fmt::Debug;
+
+fn parse(text: &str) -> ParsedValue {
+    json::parse(&mut string2parsechar("", text)).unwrap()
+}
+
+/// Checks that `value` is written as `text`, and read back from it.
+fn round_trip<T: FromJson + ToJson + PartialEq + Debug>(value: &T, text: &str) {
+    assert_eq!(json::stringify(&value.to_json()).unwrap(), json::stringify(&parse(text)).unwrap());
+    assert_eq!(&T::from_json(&parse(text)).unwrap(), value);
+}
+
+fn error<T: FromJson + Debug>(text: &str) -> String {
+    T::from_json(&parse(text)).unwrap_err().to_string()
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Renamed {
+    #[json(rename = "ID")]
+    id: u32,
+    name: String,
+}
+
+#[test]
+fn rename() {
+    round_trip(&Renamed { id: 1, name: "a".to_owned() }, r#"{"ID": 1, "name": "a"}"#);
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+#[json(rename_all = "camelCase")]
+struct Camel {
+    first_name: String,
+    #[json(rename = "last")]
+    last_name: String,
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+#[json(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Level {
+    LowValue,
+    High,
+}
+
+#[test]
+fn rename_all() {
+    round_trip(
+        &Camel { first_name: "a".to_owned(), last_name: "b".to_owned() },
+        r#"{"firstName": "a", "last": "b"}"#,
+    );
+    round_trip(&Level::LowValue, r#""LOW_VALUE""#);
+    round_trip(&Level::High, r#""HIGH""#);
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Defaults {
+    #[json(default)]
+    count: u32,
+    #[json(default)]
+    tags: Vec<String>,
+    label: Option<String>,
+}
+
+#[test]
+fn default() {
+    let value = Defaults { count: 0, tags: vec![], label: None };
+    assert_eq!(Defaults::from_json(&parse("{}")).unwrap(), value);
+    round_trip(
+        &Defaults { count: 2, tags: vec!["x".to_owned()], label: Some("l".to_owned()) },
+        r#"{"count": 2, "tags": ["x"], "label": "l"}"#,
+    );
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Skipped {
+    kept: bool,
+    #[json(skip)]
+    cache: Vec<u8>,
+}
+
+#[test]
+fn skip() {
+    round_trip(&Skipped { kept: true, cache: vec![] }, r#"{"kept": true}"#);
+    let value = Skipped { kept: true, cache: vec![1] };
+    assert_eq!(json::stringify(&value.to_json()).unwrap(), json::stringify(&parse(r#"{"kept": true}"#)).unwrap());
+    assert!(Skipped::from_json(&parse(r#"{"kept": true, "cache": [1]}"#)).unwrap().cache.is_empty());
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Point {
+    name: String,
+    #[json(flatten)]
+    position: Position,
+}
+
+#[test]
+fn flatten() {
+    round_trip(
+        &Point { name: "p".to_owned(), position: Position { x: 1, y: -2 } },
+        r#"{"name": "p", "x": 1, "y": -2}"#,
+    );
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+enum External {
+    Unit,
+    Newtype(u8),
+    Pair(u8, String),
+    Named { a: bool },
+}
+
+#[test]
+fn external_tagging() {
+    round_trip(&External::Unit, r#""Unit""#);
+    round_trip(&External::Newtype(3), r#"{"Newtype": 3}"#);
+    round_trip(&External::Pair(1, "b".to_owned()), r#"{"Pair": [1, "b"]}"#);
+    round_trip(&External::Named { a: true }, r#"{"Named": {"a": true}}"#);
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Wrap {
+    value: i32,
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+#[json(tag = "type")]
+enum Internal {
+    Unit,
+    Named { a: bool },
+    Newtype(Wrap),
+    Point(Position),
+}
+
+#[test]
+fn internal_tagging() {
+    round_trip(&Internal::Unit, r#"{"type": "Unit"}"#);
+    round_trip(&Internal::Named { a: false }, r#"{"type": "Named", "a": false}"#);
+    // A `value` member belongs to the content rather than wrapping it.
+    round_trip(&Internal::Newtype(Wrap { value: 4 }), r#"{"type": "Newtype", "value": 4}"#);
+    round_trip(&Internal::Point(Position { x: 1, y: 2 }), r#"{"type": "Point", "x": 1, "y": 2}"#);
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+#[json(tag = "t", content = "c", rename_all = "snake_case")]
+enum Adjacent {
+    Empty,
+    Number(f64),
+    Tuple(u8, u8),
+    WithFields { a: u8 },
+}
+
+#[test]
+fn adjacent_tagging() {
+    round_trip(&Adjacent::Empty, r#"{"t": "empty"}"#);
+    round_trip(&Adjacent::Number(1.5), r#"{"t": "number", "c": 1.5}"#);
+    round_trip(&Adjacent::Tuple(1, 2), r#"{"t": "tuple", "c": [1, 2]}"#);
+    round_trip(&Adjacent::WithFields { a: 1 }, r#"{"t": "with_fields", "c": {"a": 1}}"#);
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Config {
+    servers: Vec<Server>,
+    mode: Adjacent,
+}
+
+#[test]
+fn path_errors() {
+    assert_eq!(
+        error::<Config>(r#"{"servers": [{"host": "a", "port": 1}, {"host": "b", "port": "x"}], "mode": {"t": "empty"}}"#),
+        "at /servers/1/port: expected integer, found string"
+    );
+    assert_eq!(
+        error::<Config>(r#"{"servers": [{"port": 1}], "mode": {"t": "empty"}}"#),
+        "at /servers/0: missing field `host`"
+    );
+    assert_eq!(
+        error::<Config>(r#"{"servers": [], "mode": {"t": "number", "c": true}}"#),
+        "at /mode/c: expected number, found boolean"
+    );
+    assert_eq!(
+        String::from_json(&ParsedValue::Value("abc".to_owned())).unwrap_err().to_string(),
+        "at (root): expected string, found the unquoted token abc"
+    );
+    assert_eq!(error::<String>("1"), "at (root): expected string, found number");
+    assert_eq!(
+        error::<External>(r#""Other""#),
+        "at (root): unknown variant `Other`, expected one of Unit, Newtype, Pair, Named"
+    );
+}
+
+#[derive(Debug, PartialEq, FromJson, ToJson)]
+struct Generic<T = u8, const N: usize = 2> {
+    items: Vec<T>,
+}
+
+#[test]
+fn generic_defaults() {
+    round_trip(&Generic::<u8, 2> { items: vec![1, 2] }, r#"{"items": [1, 2]}"#);
+}
+
+mod renamed_crate {
+    use parsing as other;
+
+    #[derive(Debug, PartialEq, other::convert::FromJson, other::convert::ToJson)]
+    #[json(crate = "other")]
+    struct Flag(bool);
+
+    #[test]
+    fn crate_path() {
+        super::round_trip(&Flag(true), "true");
+    }
+}